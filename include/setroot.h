@@ -0,0 +1,74 @@
+/* setroot — a library for setting desktop background image.
+ * © 2025 by Michał Nazarewicz <mina86@mina86.com>
+ *
+ * setroot is free software: you can redistribute it and/or modify it under the
+ * terms of the GNU Lesser General Public License as published by the Free
+ * Software Foundation; either version 3 of the License, or (at your option) any
+ * later version.
+ *
+ * setroot is distributed in the hope that it will be useful, but WITHOUT ANY
+ * WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR
+ * A PARTICULAR PURPOSE.  See the GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * setroot.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* C interface to the setroot library.  Available when the library is built
+ * with `ffi` Cargo feature, e.g.:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * See documentation of the `setroot::ffi` module for details. */
+
+#ifndef SETROOT_H
+#define SETROOT_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle of a connection to X11 display. */
+typedef struct setroot_display setroot_display;
+
+/* Error codes.  Functions which can fail return SETROOT_OK on success or one
+ * of the negative values on failure. */
+#define SETROOT_OK                      0
+#define SETROOT_ERR_INVALID_ARGUMENT   -1
+#define SETROOT_ERR_CONNECTION         -2
+#define SETROOT_ERR_PROTOCOL           -3
+#define SETROOT_ERR_UNSUPPORTED_VISUAL -4
+#define SETROOT_ERR_IMAGE_TOO_LARGE    -5
+#define SETROOT_ERR_OTHER              -6
+
+/* How the image is fitted to the screen. */
+#define SETROOT_MODE_FILL    0
+#define SETROOT_MODE_FIT     1
+#define SETROOT_MODE_STRETCH 2
+#define SETROOT_MODE_CENTER  3
+#define SETROOT_MODE_TILE    4
+
+/* Opens connection to X11 display with given name, or one named by DISPLAY
+ * environment variable if name is NULL.  On success stores the handle in
+ * *display; it must be freed with setroot_free.  On failure sets *display to
+ * NULL. */
+int setroot_open(const char *name, setroot_display **display);
+
+/* Sets width×height RGBA image as the desktop background of the default
+ * screen.  rgba must point to width * height * 4 bytes: rows of pixels
+ * without padding, each pixel being red, green, blue and alpha bytes.  The
+ * buffer is only read during the call and remains owned by the caller. */
+int setroot_set_from_rgba(setroot_display *display, const uint8_t *rgba,
+                          uint32_t width, uint32_t height, int mode);
+
+/* Closes the connection and frees the handle.  Does nothing if display is
+ * NULL. */
+void setroot_free(setroot_display *display);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1,7 +1,11 @@
-#[derive(Debug, derive_more::Display, derive_more::From)]
+use alloc::string::String;
+
+#[derive(Debug, derive_more::Display)]
+#[cfg_attr(feature = "std", derive(derive_more::From))]
 #[non_exhaustive]
 pub enum Error {
     /// XCB error as a result of an X request.
+    #[cfg(feature = "std")]
     #[display("{}", _0)]
     #[from(xcb::Error, xcb::ConnError, xcb::ProtocolError)]
     Xcb(xcb::Error),
@@ -9,11 +13,14 @@ pub enum Error {
     /// screen.
     #[display("invalid screen number: {}", _0)]
     BadScreenNumber(i32),
-    /// Display server uses unsupported visual.  This library supports 24- or
-    /// 32-bit TrueColour visual only.
+    /// Display server uses unsupported visual.  This library supports 16-,
+    /// 24-, 30- or 32-bit TrueColour or DirectColour visual only.
+    #[cfg(feature = "std")]
     #[display("unsupported visual class: {}-bit {:?}", _0, _1)]
     UnsupportedVisual(u8, xcb::x::VisualClass),
-    /// Failed to locate visual that matches the root visual.
+    /// Failed to locate visual that matches the root visual and no other
+    /// usable visual of the root depth exists.
+    #[cfg(feature = "std")]
     #[display("could not find root visual: {}", _0)]
     CouldNotFindRootVisual(xcb::x::Visualid),
     /// Image too large.  Image dimensions must fit 16-bit unsigned integer.
@@ -22,6 +29,52 @@ pub enum Error {
     /// The image buffer size does not match image dimensions.
     #[display("{}-byte buffer does not match {}x{} image", _0, _1, _2)]
     BadBufferSize(usize, u16, u16),
+    /// Rectangle doesn’t fit inside of the pixmap.  The values are x and y
+    /// coordinates followed by width and height of the rectangle.
+    #[display(
+        "rectangle {}x{}{:+}{:+} does not fit in the pixmap",
+        _2,
+        _3,
+        _0,
+        _1
+    )]
+    RectOutOfBounds(i16, i16, u16, u16),
+    /// A `PutImage` request would exceed maximum request length supported by
+    /// the X server.  This happens if a single scanline of an image is too
+    /// long; downscaling the image should help.
+    #[display(
+        "{}-byte request exceeds maximum request length of {} bytes",
+        bytes,
+        max
+    )]
+    RequestTooLarge {
+        /// Size of the request in bytes.
+        bytes: usize,
+        /// Maximum request length supported by the server in bytes.
+        max: usize,
+    },
+    /// Pixel of an indexed image refers to a colour outside of the palette.
+    #[display("palette index {} out of range", _0)]
+    PaletteIndexOutOfRange(u8),
+    /// String doesn’t describe a valid colour.
+    #[display("invalid colour: {:?}", _0)]
+    InvalidColour(String),
+    /// Shifts passed to [`crate::img::RgbShifts::new`] describe channels
+    /// which overlap or don’t fit in `u32`.  The values are red, green and
+    /// blue shifts.
+    #[display("invalid RGB shifts: {}, {}, {}", _0, _1, _2)]
+    InvalidShifts(u8, u8, u8),
+    /// Checkerboard cell size is zero.
+    #[display("checkerboard cell size must be positive")]
+    ZeroCellSize,
+    /// X server failed to create an atom with given name.
+    #[display("could not intern {} atom", _0)]
+    CouldNotInternAtom(String),
+    /// Request updating an atom failed.  The values are name of the atom and
+    /// the error of the failed request.
+    #[cfg(feature = "std")]
+    #[display("{}: {}", _0, _1)]
+    AtomUpdate(String, xcb::Error),
     #[cfg(feature = "image")]
     #[display("{}", _0)]
     #[from]
@@ -29,6 +82,69 @@ pub enum Error {
 }
 
 
+impl Error {
+    /// Returns whether the error is caused by the display server using
+    /// a visual this library doesn’t support.
+    ///
+    /// Retrying won’t help; the program can only give up or fall back to
+    /// other means of setting the background.
+    #[cfg(feature = "std")]
+    pub fn is_unsupported_visual(&self) -> bool {
+        matches!(
+            self,
+            Self::UnsupportedVisual(..) | Self::CouldNotFindRootVisual(_)
+        )
+    }
+
+    /// Returns whether the error is caused by the image being too large.
+    ///
+    /// This covers images whose dimensions don’t fit 16-bit integers as well
+    /// as images whose scanlines exceed maximum request length.  Retrying with
+    /// a downscaled image may succeed.
+    ///
+    /// ```
+    /// let err = setroot::Error::ImageTooLarge(100_000, 10);
+    /// assert!(err.is_image_too_large());
+    /// ```
+    pub fn is_image_too_large(&self) -> bool {
+        matches!(self, Self::ImageTooLarge(..) | Self::RequestTooLarge { .. })
+    }
+
+    /// Returns whether the error is an X protocol error, i.e. the display
+    /// server rejected a request.
+    ///
+    /// Connection errors (e.g. the server going away) are not protocol errors.
+    #[cfg(feature = "std")]
+    pub fn is_protocol_error(&self) -> bool {
+        matches!(
+            self,
+            Self::Xcb(xcb::Error::Protocol(_)) |
+                Self::AtomUpdate(_, xcb::Error::Protocol(_))
+        )
+    }
+
+    /// Returns whether the error is caused by a failure of the connection to
+    /// the display server, e.g. the server going away.
+    ///
+    /// Such errors aren’t specific to the request which failed.  Reconnecting
+    /// with [`crate::Display::reopen`] and retrying may succeed.
+    ///
+    /// ```
+    /// let err = setroot::Error::from(xcb::ConnError::Connection);
+    /// assert!(err.is_connection_error());
+    /// assert!(!err.is_protocol_error());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            Self::Xcb(xcb::Error::Connection(_)) |
+                Self::AtomUpdate(_, xcb::Error::Connection(_))
+        )
+    }
+}
+
+
 /// Unrecognised screen number, i.e. negative or does not match any existing
 /// screen.
 #[derive(Debug, PartialEq, Eq, derive_more::Display)]
@@ -62,6 +178,30 @@ impl From<BadBufferSize> for Error {
 }
 
 
-impl std::error::Error for Error {}
-impl std::error::Error for BadScreenNumber {}
-impl std::error::Error for BadBufferSize {}
+/// String passed to [`crate::img::parse_colour`] doesn’t describe a valid
+/// colour.  Holds the offending string.
+#[derive(Clone, Debug, PartialEq, Eq, derive_more::Display)]
+#[display("invalid colour: {:?}", _0)]
+pub struct ColourParseError(pub String);
+
+impl From<ColourParseError> for Error {
+    fn from(err: ColourParseError) -> Error { Error::InvalidColour(err.0) }
+}
+
+/// Shifts passed to [`crate::img::RgbShifts::new`] describe channels which
+/// overlap or don’t fit in `u32`.  Holds red, green and blue shifts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, derive_more::Display)]
+#[display("invalid RGB shifts: {}, {}, {}", _0, _1, _2)]
+pub struct InvalidShifts(pub u8, pub u8, pub u8);
+
+impl From<InvalidShifts> for Error {
+    fn from(err: InvalidShifts) -> Error {
+        Error::InvalidShifts(err.0, err.1, err.2)
+    }
+}
+
+impl core::error::Error for Error {}
+impl core::error::Error for BadScreenNumber {}
+impl core::error::Error for BadBufferSize {}
+impl core::error::Error for ColourParseError {}
+impl core::error::Error for InvalidShifts {}
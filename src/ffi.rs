@@ -0,0 +1,191 @@
+//! C interface to the library.
+//!
+//! This is a thin wrapper around [`Display`] and [`crate::WallpaperBuilder`]
+//! meant for tools written in C.  Declarations of the functions are in
+//! `include/setroot.h` file.  To build a shared or static library, run:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! cargo rustc --release --features ffi --crate-type staticlib
+//! ```
+//!
+//! Functions which can fail return [`SETROOT_OK`] on success or a negative
+//! error code on failure.
+
+use core::ffi::{c_char, c_int};
+use std::borrow::Cow;
+use std::ffi::CStr;
+
+use crate::{Display, Error, img};
+
+/// The operation succeeded.
+pub const SETROOT_OK: c_int = 0;
+/// An argument is invalid, e.g. a required pointer is null or the image is
+/// too large to address.
+pub const SETROOT_ERR_INVALID_ARGUMENT: c_int = -1;
+/// Connection to the X display server failed or was lost.  See
+/// [`Error::is_connection_error`].
+pub const SETROOT_ERR_CONNECTION: c_int = -2;
+/// The X display server rejected a request.  See
+/// [`Error::is_protocol_error`].
+pub const SETROOT_ERR_PROTOCOL: c_int = -3;
+/// The X display server uses a visual the library doesn’t support.  See
+/// [`Error::is_unsupported_visual`].
+pub const SETROOT_ERR_UNSUPPORTED_VISUAL: c_int = -4;
+/// The image is too large.  See [`Error::is_image_too_large`].
+pub const SETROOT_ERR_IMAGE_TOO_LARGE: c_int = -5;
+/// Any other error.
+pub const SETROOT_ERR_OTHER: c_int = -6;
+
+/// Maps an error to one of the `SETROOT_ERR_*` codes.
+fn error_code(err: &Error) -> c_int {
+    if err.is_connection_error() {
+        SETROOT_ERR_CONNECTION
+    } else if err.is_protocol_error() {
+        SETROOT_ERR_PROTOCOL
+    } else if err.is_unsupported_visual() {
+        SETROOT_ERR_UNSUPPORTED_VISUAL
+    } else if err.is_image_too_large() {
+        SETROOT_ERR_IMAGE_TOO_LARGE
+    } else if matches!(err, Error::BadScreenNumber(_)) {
+        SETROOT_ERR_INVALID_ARGUMENT
+    } else {
+        SETROOT_ERR_OTHER
+    }
+}
+
+/// Maps a `SETROOT_MODE_*` constant to a scale mode.
+fn scale_mode(mode: c_int) -> Option<img::ScaleMode> {
+    Some(match mode {
+        0 => img::ScaleMode::Fill,
+        1 => img::ScaleMode::Fit,
+        2 => img::ScaleMode::Stretch,
+        3 => img::ScaleMode::Center,
+        4 => img::ScaleMode::Tile,
+        _ => return None,
+    })
+}
+
+/// Opens connection to X11 display.
+///
+/// `name` is the name of the display, e.g. `":1"`, or null to use the
+/// `DISPLAY` environment variable.  On success, stores handle of the
+/// connection in `*display`; the handle must be freed with [`setroot_free`].
+/// On failure, `*display` is set to null.
+///
+/// # Safety
+///
+/// `name` must be null or point to a NUL-terminated string.  `display` must
+/// be a valid pointer to writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setroot_open(
+    name: *const c_char,
+    display: *mut *mut Display,
+) -> c_int {
+    if display.is_null() {
+        return SETROOT_ERR_INVALID_ARGUMENT;
+    }
+    // SAFETY: Caller guarantees display is valid.
+    unsafe { display.write(core::ptr::null_mut()) };
+    let res = if name.is_null() {
+        Display::open()
+    } else {
+        // SAFETY: Caller guarantees name is a NUL-terminated string.
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name) => Display::open_display(name),
+            Err(_) => return SETROOT_ERR_INVALID_ARGUMENT,
+        }
+    };
+    match res {
+        Ok(handle) => {
+            // SAFETY: Caller guarantees display is valid.
+            unsafe { display.write(Box::into_raw(Box::new(handle))) };
+            SETROOT_OK
+        }
+        Err(err) => error_code(&err),
+    }
+}
+
+/// Sets an RGBA image as the desktop background of the default screen.
+///
+/// `rgba` points to `width × height` pixels stored row by row without any
+/// padding.  Each pixel is four bytes: red, green, blue and alpha in that
+/// order, in sRGB colour space.  Alpha isn’t blended with anything so the
+/// image should be opaque.  The buffer is only read during the call; the
+/// caller retains its ownership and may free or reuse it as soon as the
+/// function returns.
+///
+/// `mode` is one of `SETROOT_MODE_*` constants defined in the header (`0` for
+/// fill, `1` for fit, `2` for stretch, `3` for centre and `4` for tile).  See
+/// [`img::ScaleMode`].  Other options are defaults of [`Display::wallpaper`].
+///
+/// # Safety
+///
+/// `display` must be a handle returned by [`setroot_open`] which hasn’t been
+/// freed.  `rgba` must point to at least `width * height * 4` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setroot_set_from_rgba(
+    display: *mut Display,
+    rgba: *const u8,
+    width: u32,
+    height: u32,
+    mode: c_int,
+) -> c_int {
+    let len = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|len| len.checked_mul(4));
+    let (Some(len), Some(mode)) = (len, scale_mode(mode)) else {
+        return SETROOT_ERR_INVALID_ARGUMENT;
+    };
+    if display.is_null() || (rgba.is_null() && len != 0) {
+        return SETROOT_ERR_INVALID_ARGUMENT;
+    }
+    // SAFETY: Caller guarantees display is a live handle.
+    let display = unsafe { &*display };
+    let data = if len == 0 {
+        &[][..]
+    } else {
+        // SAFETY: Caller guarantees rgba points to len readable bytes.
+        unsafe { core::slice::from_raw_parts(rgba, len) }
+    };
+    let res = img::RgbaImage::new(width, height, Cow::Borrowed(data))
+        .and_then(|img| display.wallpaper().mode(mode).set(img));
+    match res {
+        Ok(()) => SETROOT_OK,
+        Err(err) => error_code(&err),
+    }
+}
+
+/// Closes connection to X11 display and frees the handle.
+///
+/// Does nothing if `display` is null.
+///
+/// # Safety
+///
+/// `display` must be null or a handle returned by [`setroot_open`] which
+/// hasn’t been freed yet.  The handle must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setroot_free(display: *mut Display) {
+    if !display.is_null() {
+        // SAFETY: Caller guarantees display is a live handle.
+        drop(unsafe { Box::from_raw(display) });
+    }
+}
+
+#[test]
+fn test_invalid_arguments() {
+    let data = [0u8; 4];
+    let display = core::ptr::null_mut();
+    // SAFETY: Null pointers are rejected before being dereferenced.
+    let set = |rgba, width, height, mode| unsafe {
+        setroot_set_from_rgba(display, rgba, width, height, mode)
+    };
+    assert_eq!(SETROOT_ERR_INVALID_ARGUMENT, set(data.as_ptr(), 1, 1, 0));
+    assert_eq!(SETROOT_ERR_INVALID_ARGUMENT, set(data.as_ptr(), 1, 1, 5));
+    assert_eq!(SETROOT_ERR_INVALID_ARGUMENT, set(core::ptr::null(), 1, 1, 0));
+    // SAFETY: Null pointers are rejected before being dereferenced.
+    let res = unsafe { setroot_open(core::ptr::null(), core::ptr::null_mut()) };
+    assert_eq!(SETROOT_ERR_INVALID_ARGUMENT, res);
+    // SAFETY: Freeing null is a no-op.
+    unsafe { setroot_free(display) };
+}
@@ -1,29 +1,49 @@
-use std::borrow::Cow;
+//! Image types and conversion into pixel formats used by X display servers.
+//!
+//! Without the `std` Cargo feature, the module (along with [`crate::err`]) is
+//! all that the crate provides and it builds with `#![no_std]` and `alloc`.
+//! This allows packing pixels into a framebuffer format without linking XCB.
+//! [`MemoryCanvas`] and functions which need floating point operations not
+//! available in `core` (e.g. [`srgb_encode`]) require `std`.
 
+// Helpers used by surfaces only.
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
+use alloc::borrow::Cow;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::surface::Surface;
 use crate::{Error, err};
 
 /// Definition of a pixel format used by X display server.
 ///
 /// The pixel format is defined as shift values for red, green and blue subpixel
 /// value.  The colour is represented by a `u32` value whose component channels
-/// are 8-bit values shifted to the left by corresponding shift values.
+/// are 8-bit (or, on depth-30 visuals, 10-bit) values shifted to the left by
+/// corresponding shift values.  16-bit visuals use 5-bit red and blue and
+/// 6-bit green channels.
 ///
 /// Common format for colour is `0x00_RR_GG_BB` which is described as 16, 8 and
 /// 0 shifts for red, green and blue colour components respectively.  Beware
 /// that on little endian systems (so practically on all systems), such colour
 /// is encoded as `[red, green, blue, 0]` bytes in memory.
-#[derive(
-    Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    derive_more::Display,
-    derive_more::Debug,
-)]
-#[display("RgbShifts({}, {}, {})", r, g, b)]
+///
+/// On 32-bit visuals, the remaining byte may hold alpha channel, in which case
+/// its shift is stored in the `a` field.  Otherwise, the byte is unused.
+/// Alpha channel is only supported with 8-bit colour channels.
+///
+/// The structure is non-exhaustive so it cannot be constructed directly
+/// outside of this crate.  Use one of the constants, [`Self::new`],
+/// [`Self::from_masks`] or [`Self::with_alpha`] instead.  When formatted,
+/// alpha shift and channel width are included only if present or other than
+/// eight bits respectively, e.g. `RgbShifts(16, 8, 0, 24)` for
+/// [`Self::ARGB8888`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, derive_more::Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[debug("{}", self)]
+#[non_exhaustive]
 pub struct RgbShifts {
     /// Bit shift value for red component in `u32` colour description.
     /// Typically 16.
@@ -34,13 +54,145 @@ pub struct RgbShifts {
     /// Bit shift value for red component in `u32` colour description.
     /// Typically 0.
     pub b: u8,
+    /// Bit shift value for alpha component in `u32` colour description or
+    /// `None` if the colour has no alpha channel.  Typically `None`.
+    pub a: Option<u8>,
+    /// Width in bits of red, green and blue components.  Either 5, 8 or 10.
+    /// Typically 8.  5 describes the 5/6/5 layout of 16-bit visuals in which
+    /// green component is 6-bit wide.
+    pub bits: u8,
+}
+
+impl core::fmt::Display for RgbShifts {
+    fn fmt(&self, fmtr: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let Self { r, g, b, a, bits } = self;
+        match a {
+            None => write!(fmtr, "RgbShifts({r}, {g}, {b}")?,
+            Some(a) => write!(fmtr, "RgbShifts({r}, {g}, {b}, {a}")?,
+        }
+        match bits {
+            8 => fmtr.write_str(")"),
+            5 => fmtr.write_str("; 5/6/5 bits)"),
+            _ => write!(fmtr, "; {bits} bits)"),
+        }
+    }
 }
 
 impl RgbShifts {
+    /// Format where colour is represented as `0x00_RR_GG_BB`.  This is by far
+    /// the most common format used by X display servers.
+    pub const XRGB8888: Self = Self { r: 16, g: 8, b: 0, a: None, bits: 8 };
+
+    /// Format where colour is represented as `0x00_BB_GG_RR`.
+    pub const XBGR8888: Self = Self { r: 0, g: 8, b: 16, a: None, bits: 8 };
+
+    /// Format where colour is represented as `0xAA_RR_GG_BB`.
+    pub const ARGB8888: Self = Self { r: 16, g: 8, b: 0, a: Some(24), bits: 8 };
+
+    /// Format with 10-bit channels where red occupies bits 20–29, green bits
+    /// 10–19 and blue bits 0–9.  This is used by depth-30 visuals.
+    pub const XRGB2101010: Self =
+        Self { r: 20, g: 10, b: 0, a: None, bits: 10 };
+
+    /// Format where colour is represented as 16-bit `0bRRRRR_GGGGGG_BBBBB`.
+    /// This is used by depth-16 visuals.
+    pub const RGB565: Self = Self { r: 11, g: 5, b: 0, a: None, bits: 5 };
+
+    /// Constructs shifts of a format with 8-bit red, green and blue channels
+    /// and no alpha channel.
+    ///
+    /// Unlike building the structure directly, validates the shifts.  Returns
+    /// an error if any of the channels doesn’t fit in `u32` (i.e. its shift is
+    /// greater than 24) or if channels overlap.
+    ///
+    /// ```
+    /// use setroot::img::RgbShifts;
+    ///
+    /// assert_eq!(Ok(RgbShifts::XBGR8888), RgbShifts::new(0, 8, 16));
+    /// assert!(RgbShifts::new(40, 8, 0).is_err());
+    /// ```
+    pub fn new(r: u8, g: u8, b: u8) -> Result<Self, err::InvalidShifts> {
+        let shifts = Self { r, g, b, a: None, bits: 8 };
+        let mut used = 0;
+        for (shift, bits) in shifts.channels() {
+            let mask = max_value(bits).checked_shl(u32::from(shift));
+            match mask.filter(|mask| mask >> shift == max_value(bits)) {
+                Some(mask) if used & mask == 0 => used |= mask,
+                _ => return Err(err::InvalidShifts(r, g, b)),
+            }
+        }
+        Ok(shifts)
+    }
+
+    /// Constructs shifts from red, green and blue channel masks.
+    ///
+    /// This can be used to construct the shifts from masks of an
+    /// [`xcb::x::Visualtype`].  Returns `None` if the masks don’t describe
+    /// contiguous fields of 8 or 10 bits each or 5, 6 and 5 bits respectively.
+    ///
+    /// ```
+    /// use setroot::img::RgbShifts;
+    ///
+    /// assert_eq!(
+    ///     Some(RgbShifts::XRGB8888),
+    ///     RgbShifts::from_masks(0xFF_00_00, 0x00_FF_00, 0x00_00_FF)
+    /// );
+    /// assert_eq!(
+    ///     Some(RgbShifts::XBGR8888),
+    ///     RgbShifts::from_masks(0x00_00_FF, 0x00_FF_00, 0xFF_00_00)
+    /// );
+    /// assert_eq!(
+    ///     Some(RgbShifts::XRGB2101010),
+    ///     RgbShifts::from_masks(0x3FF0_0000, 0x000F_FC00, 0x0000_03FF)
+    /// );
+    /// assert_eq!(
+    ///     Some(RgbShifts::RGB565),
+    ///     RgbShifts::from_masks(0xF8_00, 0x07_E0, 0x00_1F)
+    /// );
+    /// assert_eq!(None, RgbShifts::from_masks(0xFF_00_00, 0x3FF00, 0xFF));
+    /// assert_eq!(None, RgbShifts::from_masks(0x7C_00, 0x03_E0, 0x00_1F));
+    /// assert_eq!(None, RgbShifts::from_masks(0xFF_00_00, 0xF0_0F, 0xFF));
+    /// assert_eq!(None, RgbShifts::from_masks(0xFF_00_00, 0xFF_00, 0));
+    /// ```
+    pub fn from_masks(r_mask: u32, g_mask: u32, b_mask: u32) -> Option<Self> {
+        let (r, bits) = mask_field(r_mask)?;
+        let (g, g_bits) = mask_field(g_mask)?;
+        let (b, b_bits) = mask_field(b_mask)?;
+        let valid = match bits {
+            5 => g_bits == 6 && b_bits == 5,
+            8 | 10 => g_bits == bits && b_bits == bits,
+            _ => false,
+        };
+        valid.then_some(Self { r, g, b, a: None, bits })
+    }
+
+    /// Returns shifts with alpha channel stored in bits not used by red, green
+    /// and blue channels.
+    ///
+    /// Returns `None` if the unused bits don’t form a contiguous 8-bit field.
+    /// In particular, this is always the case for formats with 10-bit or
+    /// 5/6/5 channels.
+    ///
+    /// ```
+    /// use setroot::img::RgbShifts;
+    ///
+    /// assert_eq!(Some(RgbShifts::ARGB8888), RgbShifts::XRGB8888.with_alpha());
+    /// ```
+    pub fn with_alpha(self) -> Option<Self> {
+        let mask = self
+            .channels()
+            .into_iter()
+            .fold(!0, |mask, (shift, bits)| mask & !(max_value(bits) << shift));
+        match mask_field(mask) {
+            Some((a, 8)) => Some(Self { a: Some(a), ..self }),
+            _ => None,
+        }
+    }
+
     /// Constructs a colour representation from red, green and blue components.
     ///
     /// ```
-    /// let shifts = setroot::img::RgbShifts { r: 16, g: 8, b: 0 };
+    /// let shifts = setroot::img::RgbShifts::XRGB8888;
     /// assert_eq!(0x00_FF_F8_E7, shifts.from_rgb(0xFFFFu16, 0xF8F8, 0xE7E7));
     ///
     /// let colour = shifts.from_rgb(1u8, 2, 3);
@@ -51,32 +203,579 @@ impl RgbShifts {
     ///     assert_eq!([0, 1, 2, 3], colour.to_le_bytes());
     /// }
     /// ```
+    ///
+    /// If the format includes alpha channel, the colour is fully opaque.
+    ///
+    /// ```
+    /// let shifts = setroot::img::RgbShifts::ARGB8888;
+    /// assert_eq!(0xFF_01_02_03, shifts.from_rgb(1u8, 2, 3));
+    /// ```
+    ///
+    /// With channels which aren’t 8-bit, components are scaled to the range
+    /// of the channel.
+    ///
+    /// ```
+    /// use setroot::img::RgbShifts;
+    ///
+    /// let colour = RgbShifts::XRGB2101010.from_rgb(255u8, 255, 0);
+    /// assert_eq!(0x3FF_FFC00, colour);
+    /// let colour = RgbShifts::RGB565.from_rgb(255u8, 128, 0);
+    /// assert_eq!(0b11111_100000_00000, colour);
+    /// ```
     pub fn from_rgb<S: Subpixel>(&self, r: S, g: S, b: S) -> u32 {
-        (u32::from(r.to_u8()) << self.r) |
-            (u32::from(g.to_u8()) << self.g) |
-            (u32::from(b.to_u8()) << self.b)
+        self.pack_rgb(r, g, b) | self.a.map_or(0, |a| 0xFF << a)
+    }
+
+    /// Constructs a colour representation from red, green, blue and alpha
+    /// components.
+    ///
+    /// If the format has no alpha channel, the alpha component is ignored and
+    /// the method behaves like [`Self::from_rgb`].
+    ///
+    /// ```
+    /// use setroot::img::RgbShifts;
+    ///
+    /// let colour = RgbShifts::ARGB8888.from_rgba(1u8, 2, 3, 128);
+    /// assert_eq!(0x80_01_02_03, colour);
+    /// let colour = RgbShifts::XRGB8888.from_rgba(1u8, 2, 3, 128);
+    /// assert_eq!(0x00_01_02_03, colour);
+    /// ```
+    pub fn from_rgba<S: Subpixel>(&self, r: S, g: S, b: S, a: S) -> u32 {
+        self.pack_rgb(r, g, b) |
+            self.a.map_or(0, |shift| u32::from(a.to_u8()) << shift)
+    }
+
+    /// Constructs a colour representation from linear-light red, green and
+    /// blue components.
+    ///
+    /// Unlike [`Self::from_rgb`] which expects gamma-encoded values, the
+    /// components are intensities in 0–1 range which are first encoded with
+    /// the sRGB transfer function.  Values outside of the range are clamped.
+    /// This is slower than `from_rgb` but is the correct way to pack colours
+    /// which were computed (e.g. blended) in linear space.
+    ///
+    /// ```
+    /// let shifts = setroot::img::RgbShifts::XRGB8888;
+    /// assert_eq!(0x00_FF_BC_00, shifts.from_rgb_linear(1.0, 0.5, 0.0));
+    /// assert_eq!(0x00_FF_00_00, shifts.from_rgb_linear(2.0, -1.0, f32::NAN));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_rgb_linear(&self, r: f32, g: f32, b: f32) -> u32 {
+        self.from_rgb(srgb_encode(r), srgb_encode(g), srgb_encode(b))
+    }
+
+    /// Constructs a colour representation from hue, saturation and value.
+    ///
+    /// Hue is given in degrees and wraps modulo 360.  Saturation and value
+    /// are clamped to 0–1 range.  The conversion operates on gamma-encoded
+    /// components, i.e. the result is the same as calling [`Self::from_rgb`]
+    /// with the RGB equivalent of the colour.
+    ///
+    /// ```
+    /// let shifts = setroot::img::RgbShifts::XRGB8888;
+    /// assert_eq!(0x00_00_FF_00, shifts.from_hsv(120.0, 1.0, 1.0));
+    /// assert_eq!(0x00_FF_00_00, shifts.from_hsv(-360.0, 1.0, 1.0));
+    /// assert_eq!(0x00_80_40_40, shifts.from_hsv(0.0, 0.5, 0.5));
+    /// assert_eq!(0x00_FF_FF_FF, shifts.from_hsv(42.0, -1.0, 2.0));
+    /// ```
+    pub fn from_hsv(&self, h: f32, s: f32, v: f32) -> u32 {
+        let s = s.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0);
+        // h.rem_euclid(360.0) which isn’t available in core.
+        let h = h % 360.0;
+        let h = if h < 0.0 { h + 360.0 } else { h } / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let m = v - c;
+        let (r, g, b) = match h as u8 % 6 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        self.from_rgb(r + m, g + m, b + m)
+    }
+
+    /// Decomposes a colour representation into red, green and blue components.
+    ///
+    /// This is an inverse of [`Self::from_rgb`] for 8-bit components.  With
+    /// channels of other widths, the values are scaled to 0–255 range.
+    ///
+    /// ```
+    /// let shifts = setroot::img::RgbShifts::XRGB8888;
+    /// assert_eq!((1, 2, 3), shifts.to_rgb(shifts.from_rgb(1u8, 2, 3)));
+    /// assert_eq!((0x12, 0x34, 0x56), shifts.to_rgb(0xFF_12_34_56));
+    ///
+    /// let shifts = setroot::img::RgbShifts::XBGR8888;
+    /// assert_eq!((0x56, 0x34, 0x12), shifts.to_rgb(0x00_12_34_56));
+    /// ```
+    pub fn to_rgb(&self, colour: u32) -> (u8, u8, u8) {
+        let get = |(shift, bits): (u8, u8)| {
+            if bits == 8 {
+                return (colour >> shift) as u8;
+            }
+            let max = max_value(bits);
+            let value = (colour >> shift) & max;
+            ((value * 255 + max / 2) / max) as u8
+        };
+        let [r, g, b] = self.channels().map(get);
+        (r, g, b)
+    }
+
+    /// Converts pixels in place from format described by `from` into format
+    /// described by `self`.
+    ///
+    /// Each pixel is decomposed with [`Self::to_rgb`] of `from` and packed
+    /// again with [`Self::from_rgb`].  This allows reusing a buffer prepared
+    /// for one visual (e.g. pixels of a [`PreparedImage`]) with another, for
+    /// example after reconnecting to a different X server, without having to
+    /// convert the source image again.  Alpha isn’t preserved and, since
+    /// components are converted through 8-bit values, neither is precision of
+    /// wider channels.  Does nothing if the formats are the same.
+    ///
+    /// ```
+    /// use setroot::img::RgbShifts;
+    ///
+    /// let mut data = [0x00_01_02_03, 0x00_04_05_06];
+    /// RgbShifts::XBGR8888.repack_buffer(RgbShifts::XRGB8888, &mut data);
+    /// assert_eq!([0x00_03_02_01, 0x00_06_05_04], data);
+    /// ```
+    pub fn repack_buffer(&self, from: RgbShifts, data: &mut [u32]) {
+        if from != *self {
+            for px in data {
+                let (r, g, b) = from.to_rgb(*px);
+                *px = self.from_rgb(r, g, b);
+            }
+        }
     }
 
     /// Constructs a greyscale colour representation from luma value.
     ///
     /// Due to minor optimisation, it returns slightly different value than
     /// `self.from_rgb(l, l, l)` would.  Specifically, luma is in addition
-    /// copied the unused byte of the colour.
+    /// copied the unused byte of the colour.  If the format includes alpha
+    /// channel, the colour is fully opaque.  The optimisation isn’t used for
+    /// formats whose channels aren’t 8-bit.
     ///
     /// ```
-    /// let shifts = setroot::img::RgbShifts { r: 16, g: 8, b: 0 };
-    /// assert_eq!(0x42_42_42_42, shifts.from_luma(0x42u8));
+    /// use setroot::img::RgbShifts;
+    ///
+    /// assert_eq!(0x42_42_42_42, RgbShifts::XRGB8888.from_luma(0x42u8));
+    /// assert_eq!(0xFF_42_42_42, RgbShifts::ARGB8888.from_luma(0x42u8));
     /// ```
     pub fn from_luma<S: Subpixel>(&self, luma: S) -> u32 {
-        u32::from(luma.to_u8()) * 0x0101_0101
+        if self.bits != 8 {
+            return self.from_rgb(luma, luma, luma);
+        }
+        let colour = u32::from(luma.to_u8()) * 0x0101_0101;
+        self.a.map_or(colour, |a| colour | (0xFF << a))
+    }
+
+    /// Returns shift and width of red, green and blue channels.
+    fn channels(&self) -> [(u8, u8); 3] {
+        let g_bits = if self.bits == 5 { 6 } else { self.bits };
+        [(self.r, self.bits), (self.g, g_bits), (self.b, self.bits)]
+    }
+
+    /// Returns shift and width of red, green, blue and alpha channels.
+    ///
+    /// If there’s no alpha channel, its width is zero.
+    fn fields(&self) -> [(u8, u8); 4] {
+        let [r, g, b] = self.channels();
+        [r, g, b, self.a.map_or((0, 0), |a| (a, 8))]
+    }
+
+    /// Packs red, green and blue components without the alpha channel.
+    #[inline]
+    fn pack_rgb<S: Subpixel>(&self, r: S, g: S, b: S) -> u32 {
+        if self.bits == 8 {
+            return (u32::from(r.to_u8()) << self.r) |
+                (u32::from(g.to_u8()) << self.g) |
+                (u32::from(b.to_u8()) << self.b);
+        }
+        let [r_field, g_field, b_field] = self.channels();
+        let pack =
+            |value: S, (shift, bits): (u8, u8)| component(value, bits) << shift;
+        pack(r, r_field) | pack(g, g_field) | pack(b, b_field)
+    }
+}
+
+/// Rounds a value to the nearest integer with ties rounded away from zero.
+///
+/// Equivalent to `f64::round` which isn’t available in `core`.
+fn round(value: f64) -> f64 {
+    // Values this large (as well as infinities and NaN) are already integers.
+    #[allow(clippy::neg_cmp_op_on_partial_ord)]
+    if !(value.abs() < (1u64 << 52) as f64) {
+        return value;
+    }
+    let int = value as i64 as f64;
+    match value - int {
+        frac if frac >= 0.5 => int + 1.0,
+        frac if frac <= -0.5 => int - 1.0,
+        _ => int,
+    }
+}
+
+#[test]
+fn test_round() {
+    for value in [0.0f64, 0.49999997, 0.5, 1.5, 2.5, -0.5, -1.7, 1e20, 1e300] {
+        assert_eq!(value.round(), round(value), "{value}");
+        let value = value / 3.0;
+        assert_eq!(value.round(), round(value), "{value}");
+    }
+    assert!(round(f64::NAN).is_nan());
+}
+
+/// Returns maximum value of a `bits`-wide channel.
+fn max_value(bits: u8) -> u32 { (1 << bits) - 1 }
+
+/// Scales a component into range of a `bits`-wide channel.
+#[inline]
+fn component<S: Subpixel>(value: S, bits: u8) -> u32 {
+    match bits {
+        8 => u32::from(value.to_u8()),
+        10 => u32::from(value.to_u10()),
+        _ => u32::from(value.to_u16()) >> (16 - bits),
+    }
+}
+
+/// Returns position and width of a contiguous bit field described by given
+/// mask or `None` if the mask doesn’t describe such a field.
+fn mask_field(mask: u32) -> Option<(u8, u8)> {
+    let shift = mask.trailing_zeros();
+    let value = mask.checked_shr(shift)?;
+    value
+        .checked_add(1)
+        .is_some_and(u32::is_power_of_two)
+        .then_some((shift as u8, value.count_ones() as u8))
+}
+
+#[test]
+fn test_depth_30() {
+    // Masks of a typical depth-30 TrueColor visual.
+    let shifts = RgbShifts::from_masks(0x3FF0_0000, 0x000F_FC00, 0x0000_03FF);
+    let shifts = shifts.unwrap();
+    assert_eq!(RgbShifts::XRGB2101010, shifts);
+    assert_eq!(None, shifts.with_alpha());
+    assert_eq!("RgbShifts(20, 10, 0; 10 bits)", alloc::format!("{shifts}"));
+
+    assert_eq!(0x3FFF_FFFF, shifts.from_rgb(255u8, 255, 255));
+    assert_eq!(0x3FFF_FFFF, shifts.from_rgb(0xFFFFu16, 0xFFFF, 0xFFFF));
+    assert_eq!(0x3FFF_FFFF, shifts.from_rgb(1.0f32, 1.0, 1.0));
+    assert_eq!(0x3FFF_FFFF, shifts.from_luma(255u8));
+    assert_eq!(0x3FF_00000, shifts.from_rgb(1.0f32, 0.0, 0.0));
+    assert_eq!((255, 255, 255), shifts.to_rgb(0xFFFF_FFFF));
+    for value in 0..=255 {
+        let colour = shifts.from_rgb(value, 0, value);
+        assert_eq!((value, 0, value), shifts.to_rgb(colour));
+    }
+}
+
+#[test]
+fn test_depth_16() {
+    // Masks of a typical depth-16 TrueColor visual.
+    let shifts = RgbShifts::from_masks(0xF800, 0x07E0, 0x001F).unwrap();
+    assert_eq!(RgbShifts::RGB565, shifts);
+    assert_eq!(None, shifts.with_alpha());
+    assert_eq!("RgbShifts(11, 5, 0; 5/6/5 bits)", alloc::format!("{shifts}"));
+
+    assert_eq!(0xFFFF, shifts.from_rgb(255u8, 255, 255));
+    assert_eq!(0xFFFF, shifts.from_luma(1.0f32));
+    assert_eq!(0x07E0, shifts.from_rgb(0u8, 255, 0));
+    assert_eq!((255, 255, 255), shifts.to_rgb(0xFFFF));
+    assert_eq!((0, 255, 0), shifts.to_rgb(0x07E0));
+    // 0b10000_100000_10000, i.e. halfway in each channel.
+    assert_eq!((132, 130, 132), shifts.to_rgb(0x8410));
+}
+
+#[test]
+fn test_repack_buffer() {
+    let mut data = [0x80_FF_80_00, 0x00_00_00_FF];
+    RgbShifts::RGB565.repack_buffer(RgbShifts::ARGB8888, &mut data);
+    assert_eq!([0xFC00, 0x001F], data);
+    RgbShifts::ARGB8888.repack_buffer(RgbShifts::RGB565, &mut data);
+    assert_eq!([0xFF_FF_82_00, 0xFF_00_00_FF], data);
+    RgbShifts::ARGB8888.repack_buffer(RgbShifts::ARGB8888, &mut data);
+    assert_eq!([0xFF_FF_82_00, 0xFF_00_00_FF], data);
+}
+
+#[test]
+fn test_rgb_shifts_new() {
+    assert_eq!(Ok(RgbShifts::XRGB8888), RgbShifts::new(16, 8, 0));
+    assert_eq!(
+        Ok(RgbShifts { r: 24, g: 16, b: 8, a: None, bits: 8 }),
+        RgbShifts::new(24, 16, 8)
+    );
+
+    let invalid = |r, g, b| Err(err::InvalidShifts(r, g, b));
+    assert_eq!(invalid(25, 8, 0), RgbShifts::new(25, 8, 0));
+    assert_eq!(invalid(16, 8, 255), RgbShifts::new(16, 8, 255));
+    assert_eq!(invalid(8, 8, 0), RgbShifts::new(8, 8, 0));
+    assert_eq!(invalid(16, 4, 0), RgbShifts::new(16, 4, 0));
+    assert_eq!(invalid(0, 8, 0), RgbShifts::new(0, 8, 0));
+}
+
+
+/// Parses a colour specification.
+///
+/// Accepts `#RGB` and `#RRGGBB` hexadecimal notations as well as basic CSS
+/// colour keywords such as `black`, `white` or `navy`.  Keywords are matched
+/// case-insensitively.  Returns the colour as `(red, green, blue)` tuple of
+/// sRGB components which can be passed to [`RgbShifts::from_rgb`] or
+/// [`crate::Display::set_solid_background`].
+///
+/// ```
+/// use setroot::img::parse_colour;
+///
+/// assert_eq!(Ok((0x22, 0x33, 0x44)), parse_colour("#223344"));
+/// assert_eq!(Ok((0xFF, 0x00, 0xCC)), parse_colour("#f0c"));
+/// assert_eq!(Ok((0, 0, 128)), parse_colour("Navy"));
+/// assert!(parse_colour("#12345").is_err());
+/// assert!(parse_colour("burgundy").is_err());
+/// ```
+pub fn parse_colour(spec: &str) -> Result<(u8, u8, u8), err::ColourParseError> {
+    const KEYWORDS: [(&str, (u8, u8, u8)); 18] = [
+        ("black", (0, 0, 0)),
+        ("silver", (192, 192, 192)),
+        ("gray", (128, 128, 128)),
+        ("grey", (128, 128, 128)),
+        ("white", (255, 255, 255)),
+        ("maroon", (128, 0, 0)),
+        ("red", (255, 0, 0)),
+        ("purple", (128, 0, 128)),
+        ("fuchsia", (255, 0, 255)),
+        ("magenta", (255, 0, 255)),
+        ("green", (0, 128, 0)),
+        ("lime", (0, 255, 0)),
+        ("olive", (128, 128, 0)),
+        ("yellow", (255, 255, 0)),
+        ("navy", (0, 0, 128)),
+        ("blue", (0, 0, 255)),
+        ("teal", (0, 128, 128)),
+        ("aqua", (0, 255, 255)),
+    ];
+
+    let colour = match spec.strip_prefix('#') {
+        Some(digits) if digits.bytes().all(|ch| ch.is_ascii_hexdigit()) => {
+            let value = u32::from_str_radix(digits, 16).ok();
+            match digits.len() {
+                3 => value.map(|v| {
+                    let get = |shift: u32| ((v >> shift) & 0xF) as u8 * 17;
+                    (get(8), get(4), get(0))
+                }),
+                6 => value.map(|v| ((v >> 16) as u8, (v >> 8) as u8, v as u8)),
+                _ => None,
+            }
+        }
+        Some(_) => None,
+        None => KEYWORDS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(spec))
+            .map(|&(_, colour)| colour),
+    };
+    colour.ok_or_else(|| err::ColourParseError(spec.into()))
+}
+
+
+/// Converts a gamma-encoded 8-bit sRGB component into linear-light intensity
+/// in 0–1 range.
+#[cfg(feature = "std")]
+pub fn srgb_decode(value: u8) -> f32 {
+    let value = f32::from(value) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear-light intensity in 0–1 range into an 8-bit sRGB component.
+///
+/// Values outside of the range are clamped.  NaN is mapped to zero.
+#[cfg(feature = "std")]
+pub fn srgb_encode(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    // NaN converts to zero.
+    (value * 255.0).round() as u8
+}
+
+/// Blends two sRGB colours in linear-light space.
+///
+/// Returns `a * (1 - t) + b * t` where the colours are first decoded into
+/// linear intensities and the result encoded back into sRGB.  `t` is clamped
+/// to 0–1 range.  Compared to blending gamma-encoded values (as done by
+/// gradients and crossfade), this avoids dark, muddy mid-tones at the cost of
+/// being slower.
+///
+/// ```
+/// use setroot::img::blend_linear;
+///
+/// let (red, green) = ((255, 0, 0), (0, 255, 0));
+/// assert_eq!(red, blend_linear(red, green, 0.0));
+/// assert_eq!(green, blend_linear(red, green, 1.0));
+/// // Naïve blending would produce (128, 128, 0).
+/// assert_eq!((188, 188, 0), blend_linear(red, green, 0.5));
+/// ```
+#[cfg(feature = "std")]
+pub fn blend_linear(a: (u8, u8, u8), b: (u8, u8, u8), t: f32) -> (u8, u8, u8) {
+    let t = t.clamp(0.0, 1.0);
+    let mix = |a: u8, b: u8| {
+        let (a, b) = (srgb_decode(a), srgb_decode(b));
+        srgb_encode(a + (b - a) * t)
+    };
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_srgb_round_trip() {
+    for value in 0..=255 {
+        assert_eq!(value, srgb_encode(srgb_decode(value)));
+    }
+    assert_eq!(0.0, srgb_decode(0));
+    assert_eq!(1.0, srgb_decode(255));
+    assert_eq!(0, srgb_encode(f32::NAN));
+}
+
+
+/// Returns average colour of an image.
+///
+/// The average is computed over gamma-encoded sRGB components.  Paired with
+/// [`crate::RootPixmap::fill_solid`] this can be used to pick colour of bars
+/// around an image drawn with [`ScaleMode::Fit`] mode.  Returns black for an
+/// empty image.
+///
+/// ```
+/// use setroot::img::{RgbImage, average_colour};
+///
+/// let data: &[u8] = &[255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0, 0];
+/// let img = RgbImage::new(2, 2, data.into()).unwrap();
+/// assert_eq!((64, 64, 64), average_colour(img).unwrap());
+/// ```
+pub fn average_colour<'a>(
+    img: impl IntoXBuffer<'a>,
+) -> crate::Result<(u8, u8, u8)> {
+    let pixels = decode_pixels(img)?;
+    let mut sums = [0u64; 3];
+    for &(r, g, b) in &pixels {
+        sums[0] += u64::from(r);
+        sums[1] += u64::from(g);
+        sums[2] += u64::from(b);
+    }
+    let count = pixels.len().max(1) as u64;
+    let [r, g, b] = sums.map(|sum| ((sum + count / 2) / count) as u8);
+    Ok((r, g, b))
+}
+
+/// Returns dominant colour of an image.
+///
+/// Colours are quantised into 512 buckets (by taking three most significant
+/// bits of each component) and the average colour of the most populated
+/// bucket is returned.  Compared to [`average_colour`], this picks a colour
+/// which actually appears in the image rather than a mix of all of them.
+/// Returns black for an empty image.
+///
+/// ```
+/// use setroot::img::{RgbImage, dominant_colour};
+///
+/// let data: &[u8] = &[255, 0, 0, 0, 0, 255, 0, 0, 250, 0, 0, 0];
+/// let img = RgbImage::new(2, 2, data.into()).unwrap();
+/// assert_eq!((0, 0, 253), dominant_colour(img).unwrap());
+/// ```
+pub fn dominant_colour<'a>(
+    img: impl IntoXBuffer<'a>,
+) -> crate::Result<(u8, u8, u8)> {
+    let mut buckets = vec![(0u32, [0u64; 3]); 512];
+    for (r, g, b) in decode_pixels(img)? {
+        let idx = usize::from(r >> 5) << 6 |
+            usize::from(g >> 5) << 3 |
+            usize::from(b >> 5);
+        let (count, sums) = &mut buckets[idx];
+        *count += 1;
+        sums[0] += u64::from(r);
+        sums[1] += u64::from(g);
+        sums[2] += u64::from(b);
     }
+    // max_by_key returns the last maximum; iterate in reverse so that ties
+    // resolve to the darker bucket deterministically.
+    let (count, sums) = buckets
+        .into_iter()
+        .rev()
+        .max_by_key(|(count, _)| *count)
+        .unwrap_or_default();
+    let count = u64::from(count.max(1));
+    let [r, g, b] = sums.map(|sum| ((sum + count / 2) / count) as u8);
+    Ok((r, g, b))
+}
+
+/// Converts an image into a vector of `(r, g, b)` tuples.
+fn decode_pixels<'a>(
+    img: impl IntoXBuffer<'a>,
+) -> crate::Result<Vec<(u8, u8, u8)>> {
+    let shifts = RgbShifts::XRGB8888;
+    let buffer = img.into_x_buffer(shifts)?;
+    let (pixels, _) = buffer.as_ref().as_chunks::<4>();
+    Ok(pixels.iter().map(|&px| shifts.to_rgb(u32::from_ne_bytes(px))).collect())
+}
+
+#[test]
+fn test_dominant_colour() {
+    let img = |data: &'static [u8]| {
+        RgbImage::new(data.len() as u32 / 3, 1, data.into()).unwrap()
+    };
+    assert_eq!((0, 0, 0), dominant_colour(img(&[])).unwrap());
+    assert_eq!((0, 0, 0), average_colour(img(&[])).unwrap());
+    // Tie between two buckets resolves to the darker one.
+    assert_eq!(
+        (0, 0, 0),
+        dominant_colour(img(&[0, 0, 0, 255, 255, 255])).unwrap()
+    );
+    let data = &[10, 20, 40, 12, 22, 42, 200, 200, 200];
+    assert_eq!((11, 21, 41), dominant_colour(img(data)).unwrap());
+    assert_eq!((74, 81, 94), average_colour(img(data)).unwrap());
 }
 
 
+/// Helper for bounds which are required only with `rayon` feature.
+mod parallel {
+    #[cfg(feature = "rayon")]
+    pub trait MaybeSync: Send + Sync {}
+    #[cfg(feature = "rayon")]
+    impl<T: Send + Sync> MaybeSync for T {}
+
+    #[cfg(not(feature = "rayon"))]
+    pub trait MaybeSync {}
+    #[cfg(not(feature = "rayon"))]
+    impl<T> MaybeSync for T {}
+}
+
 /// A type of a single colour component.
-pub trait Subpixel: bytemuck::Pod {
+///
+/// With `rayon` Cargo feature enabled, the components must also be `Send` and
+/// `Sync` so that images can be converted in parallel.
+pub trait Subpixel: bytemuck::Pod + parallel::MaybeSync {
     /// Converts the component value into one in 0–255 range.
     fn to_u8(self) -> u8;
+
+    /// Converts the component value into one in 0–65535 range.
+    ///
+    /// This is used where more precision than eight bits is beneficial, e.g.
+    /// when dithering.  By default, scales result of [`Self::to_u8`].
+    fn to_u16(self) -> u16 { u16::from(self.to_u8()) * 257 }
+
+    /// Converts the component value into one in 0–1023 range.
+    ///
+    /// This is used with visuals which use 10-bit colour channels.  By
+    /// default, takes ten most significant bits of [`Self::to_u16`].
+    fn to_u10(self) -> u16 { self.to_u16() >> 6 }
 }
 
 impl Subpixel for u8 {
@@ -104,6 +803,9 @@ impl Subpixel for u16 {
     /// assert_eq!(0x12, 0x1234u16.to_u8());
     /// ```
     fn to_u8(self) -> u8 { (self >> 8) as u8 }
+
+    /// Returns the value unchanged.
+    fn to_u16(self) -> u16 { self }
 }
 
 impl Subpixel for f32 {
@@ -130,7 +832,63 @@ impl Subpixel for f32 {
         } else if self <= 0.0 {
             0
         } else {
-            (self * 255.0).round() as u8
+            round(f64::from(self * 255.0)) as u8
+        }
+    }
+
+    /// Clamps value to 0.0–1.0 scale and then scales to 0–65535 integer value.
+    fn to_u16(self) -> u16 {
+        // Handle NaNs.
+        #[allow(clippy::neg_cmp_op_on_partial_ord)]
+        if !(self < 1.0) {
+            u16::MAX
+        } else if self <= 0.0 {
+            0
+        } else {
+            round(f64::from(self * 65535.0)) as u16
+        }
+    }
+}
+
+impl Subpixel for f64 {
+    /// Clamps value to 0.0–1.0 scale and then scales to 0–255 integer value.
+    ///
+    /// Negative values and values greater than one are considered invalid thus
+    /// they are clamped to the valid range boundaries.  NaN is treated as one.
+    ///
+    /// ```
+    /// use setroot::img::Subpixel;
+    ///
+    /// assert_eq!(255, 1.0f64.to_u8());
+    /// assert_eq!(0, 0.0f64.to_u8());
+    /// assert_eq!(128, 0.5f64.to_u8());
+    ///
+    /// assert_eq!(255, 1.5f64.to_u8());
+    /// assert_eq!(0, (-1.0f64).to_u8());
+    /// assert_eq!(255, f64::NAN.to_u8());
+    /// ```
+    fn to_u8(self) -> u8 {
+        // Handle NaNs.
+        #[allow(clippy::neg_cmp_op_on_partial_ord)]
+        if !(self < 1.0) {
+            255
+        } else if self <= 0.0 {
+            0
+        } else {
+            round(self * 255.0) as u8
+        }
+    }
+
+    /// Clamps value to 0.0–1.0 scale and then scales to 0–65535 integer value.
+    fn to_u16(self) -> u16 {
+        // Handle NaNs.
+        #[allow(clippy::neg_cmp_op_on_partial_ord)]
+        if !(self < 1.0) {
+            u16::MAX
+        } else if self <= 0.0 {
+            0
+        } else {
+            round(self * 65535.0) as u16
         }
     }
 }
@@ -156,6 +914,43 @@ pub trait IntoXBuffer<'a> {
         self,
         rgb_shifts: RgbShifts,
     ) -> crate::Result<Self::Buffer>;
+
+    /// Writes the image in format supported by the X display server into
+    /// given buffer.
+    ///
+    /// Behaves like [`Self::into_x_buffer`] except that rather than allocating
+    /// a new buffer, it clears `out` and stores pixels in it.  This allows the
+    /// allocation to be reused when converting multiple images.
+    ///
+    /// The default implementation calls [`Self::into_x_buffer`] and copies the
+    /// result.  Image types which can write pixels directly should override
+    /// it.
+    ///
+    /// ```
+    /// # use setroot::img::{RgbImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// let mut buffer = Vec::new();
+    /// for (data, want) in [([1u8, 2, 3], 0x010203), ([4, 5, 6], 0x040506)] {
+    ///     let img = RgbImage::new(1, 1, (&data[..]).into()).unwrap();
+    ///     img.into_x_buffer_in(RgbShifts::XRGB8888, &mut buffer).unwrap();
+    ///     assert_eq!(&[want], &buffer[..]);
+    /// }
+    /// ```
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result
+    where
+        Self: Sized,
+    {
+        let buffer = self.into_x_buffer(rgb_shifts)?;
+        let (chunks, _) = buffer.as_ref().as_chunks();
+        out.clear();
+        out.extend(chunks.iter().copied().map(u32::from_ne_bytes));
+        Ok(())
+    }
 }
 
 /// Converts image dimensions into `(u16, u16)` pair.  Returns an error if
@@ -184,22 +979,853 @@ pub fn new_dimensions<T: Copy + Into<u32> + TryInto<u16>>(
 }
 
 
+/// Describes how an image is fitted into an area it’s drawn onto.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ScaleMode {
+    /// Scales the image preserving its aspect ratio such that it covers the
+    /// entire area.  Parts of the image which don’t fit are cropped.
+    #[default]
+    Fill,
+    /// Scales the image preserving its aspect ratio such that it fits inside
+    /// of the area.  Parts of the area not covered by the image are left
+    /// untouched.
+    Fit,
+    /// Scales the image to match the area’s dimensions exactly disregarding
+    /// its aspect ratio.
+    Stretch,
+    /// Centres the image without scaling it.  If the image is larger than the
+    /// area, it’s cropped; if it’s smaller, parts of the area not covered by
+    /// the image are left untouched.
+    Center,
+    /// Repeats the image without scaling it such that it covers the entire
+    /// area.  The first tile is placed in the top-left corner.  See
+    /// [`crate::RootPixmap::put_image_tiled`] for tiling with an offset.
+    Tile,
+}
 
-#[derive(Clone)]
-struct InnerImage<'a, S: Clone> {
-    dimensions: (u16, u16),
-    data: Cow<'a, [S]>,
+/// Describes where an image is anchored within an area it’s drawn onto.
+///
+/// Used with [`ScaleMode::Fill`], [`ScaleMode::Fit`] and [`ScaleMode::Center`]
+/// modes to position an image which doesn’t match the area exactly.  With
+/// `Fit` and `Center`, determines which parts of the area are left uncovered;
+/// with `Fill` and `Center`, which parts of the image are cropped.  See
+/// [`crate::RootPixmap::put_image_scaled_with_gravity`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Gravity {
+    /// Aligns the image with the top-left corner of the area.
+    NorthWest,
+    /// Aligns the image with the top edge of the area centring it
+    /// horizontally.
+    North,
+    /// Aligns the image with the top-right corner of the area.
+    NorthEast,
+    /// Aligns the image with the left edge of the area centring it
+    /// vertically.
+    West,
+    /// Centres the image in the area.
+    #[default]
+    Center,
+    /// Aligns the image with the right edge of the area centring it
+    /// vertically.
+    East,
+    /// Aligns the image with the bottom-left corner of the area.
+    SouthWest,
+    /// Aligns the image with the bottom edge of the area centring it
+    /// horizontally.
+    South,
+    /// Aligns the image with the bottom-right corner of the area.
+    SouthEast,
 }
 
-impl<'a, S: Clone> InnerImage<'a, S> {
-    pub fn new(
-        width: u32,
-        height: u32,
-        data: Cow<'a, [S]>,
-        channels: usize,
-    ) -> Result<Self, Error> {
-        let (width, height) = new_dimensions((width, height))?;
-        if usize::from(width) * usize::from(height) * channels == data.len() {
+impl Gravity {
+    /// Returns position of an object within an area given horizontal and
+    /// vertical space left around it (which is negative if the object is
+    /// larger than the area).  When centring, odd leftover pixel goes after
+    /// the object.
+    fn offset(self, free_x: i64, free_y: i64) -> (i64, i64) {
+        use Gravity::*;
+        let x = match self {
+            NorthWest | West | SouthWest => 0,
+            North | Center | South => free_x / 2,
+            NorthEast | East | SouthEast => free_x,
+        };
+        let y = match self {
+            NorthWest | North | NorthEast => 0,
+            West | Center | East => free_y / 2,
+            SouthWest | South | SouthEast => free_y,
+        };
+        (x, y)
+    }
+}
+
+/// Filter used when resizing images.
+///
+/// Filters other than [`Filter::Nearest`] require the `resize` Cargo feature.
+/// Without it, nearest-neighbour filter is used regardless of the selected
+/// filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// Nearest-neighbour filter.  It’s the fastest but produces blocky results
+    /// when enlarging and aliasing artefacts when shrinking images.
+    #[default]
+    Nearest,
+    /// Bilinear (triangle) filter.
+    Bilinear,
+    /// Lanczos filter with window of three lobes.  It’s the slowest but
+    /// produces sharpest results.  Good choice for photographs.
+    Lanczos3,
+}
+
+/// Position and size of a scaled image relative to the area it’s drawn onto.
+///
+/// The coordinates and dimensions may lie outside of the area (e.g. when image
+/// is cropped) so they use wider types than the X protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Placement {
+    pub x: i64,
+    pub y: i64,
+    pub width: u64,
+    pub height: u64,
+}
+
+impl ScaleMode {
+    /// Calculates where an image of given dimensions should be placed inside
+    /// an area of given dimensions.
+    ///
+    /// `gravity` anchors the scaled image within the area.  For
+    /// [`ScaleMode::Tile`] it’s ignored and the function returns placement of
+    /// the first, top-left tile.
+    pub(crate) fn place(
+        self,
+        img: (u16, u16),
+        area: (u16, u16),
+        gravity: Gravity,
+    ) -> Placement {
+        let (iw, ih) = (u64::from(img.0), u64::from(img.1));
+        let (aw, ah) = (u64::from(area.0), u64::from(area.1));
+        let (width, height) = match self {
+            Self::Stretch => (aw, ah),
+            Self::Center | Self::Tile => (iw, ih),
+            _ if iw == 0 || ih == 0 => (iw, ih),
+            // Image is relatively wider than the area, thus Fill needs to
+            // match heights and Fit needs to match widths.
+            Self::Fill if iw * ah > ih * aw => ((iw * ah).div_ceil(ih), ah),
+            Self::Fit if iw * ah > ih * aw => (aw, (ih * aw).div_ceil(iw)),
+            Self::Fill => (aw, (ih * aw).div_ceil(iw)),
+            Self::Fit => ((iw * ah).div_ceil(ih), ah),
+        };
+        let (x, y) = if self == Self::Tile {
+            (0, 0)
+        } else {
+            gravity.offset(aw as i64 - width as i64, ah as i64 - height as i64)
+        };
+        Placement { x, y, width, height }
+    }
+}
+
+/// Resamples an image using given filter.
+///
+/// `src` is a `src_dim.0`×`src_dim.1` image which is scaled to dimensions and
+/// position described by `placement`.  Only the part of the scaled image which
+/// lies inside of an `area`-sized rectangle is computed.  Returns position and
+/// dimensions of that visible part alongside its pixels or `None` if nothing
+/// is visible.
+///
+/// `rgb_shifts` describes format of the pixels.  Filters other than
+/// nearest-neighbour interpolate each of its channels separately.  Without
+/// the `resize` feature, always uses nearest-neighbour filter.
+pub(crate) fn resample(
+    src: &[u32],
+    src_dim: (u16, u16),
+    placement: Placement,
+    area: (u16, u16),
+    rgb_shifts: RgbShifts,
+    filter: Filter,
+) -> Option<(u16, u16, u16, u16, Vec<u32>)> {
+    fn range(pos: i64, len: u64, limit: u16) -> Option<(u16, u16)> {
+        let start = pos.max(0);
+        let end = (pos + len as i64).min(i64::from(limit));
+        (start < end).then(|| (start as u16, (end - start) as u16))
+    }
+
+    let (x, width) = range(placement.x, placement.width, area.0)?;
+    let (y, height) = range(placement.y, placement.height, area.1)?;
+    let (src_w, src_h) = (u64::from(src_dim.0), u64::from(src_dim.1));
+
+    #[cfg(feature = "resize")]
+    if filter != Filter::Nearest &&
+        (placement.width, placement.height) != (src_w, src_h)
+    {
+        let visible = (x, y, width, height);
+        let data = resample_filtered(
+            src, src_dim, placement, visible, rgb_shifts, filter,
+        );
+        return Some((x, y, width, height, data));
+    }
+    #[cfg(not(feature = "resize"))]
+    let _ = (rgb_shifts, filter);
+
+    let columns = (0..u64::from(width))
+        .map(|col| {
+            let col = (col + u64::from(x)) as i64 - placement.x;
+            (col as u64 * src_w / placement.width) as usize
+        })
+        .collect::<Vec<_>>();
+    let mut data = Vec::with_capacity(usize::from(width) * usize::from(height));
+    for row in 0..u64::from(height) {
+        let row = (row + u64::from(y)) as i64 - placement.y;
+        let row = (row as u64 * src_h / placement.height) as usize;
+        let row = &src[row * usize::from(src_dim.0)..];
+        data.extend(columns.iter().map(|&col| row[col]));
+    }
+    Some((x, y, width, height, data))
+}
+
+/// Blurs an image in place using a box blur of given radius.
+///
+/// The blur is separable: rows are blurred first, then columns.  Each output
+/// pixel is an average of the `2 * radius + 1` pixels surrounding it with
+/// edge pixels repeated past the image’s borders.  Each channel described by
+/// `rgb_shifts` (including alpha if present) is averaged separately such that
+/// channels which don’t align with bytes (e.g. in 30- or 16-bit formats) are
+/// handled correctly.  Bits outside of the channels are cleared.  Does nothing
+/// if `radius` is zero.
+pub(crate) fn box_blur(
+    data: &mut [u32],
+    dim: (u16, u16),
+    rgb_shifts: RgbShifts,
+    radius: u16,
+) {
+    let (width, height) = (usize::from(dim.0), usize::from(dim.1));
+    if radius == 0 || width == 0 || height == 0 {
+        return;
+    }
+    let fields = rgb_shifts.fields();
+    let radius = usize::from(radius);
+    let mut line = Vec::with_capacity(width.max(height));
+    for row in data.chunks_exact_mut(width) {
+        line.clear();
+        line.extend_from_slice(row);
+        blur_line(&line, radius, fields, |idx, px| row[idx] = px);
+    }
+    for col in 0..width {
+        line.clear();
+        line.extend(data[col..].iter().step_by(width).copied());
+        blur_line(&line, radius, fields, |idx, px| {
+            data[idx * width + col] = px
+        });
+    }
+}
+
+/// Blurs a single row or column of pixels passing the results to `put`.
+///
+/// `fields` lists shift and width of each channel as returned by
+/// [`RgbShifts::fields`].
+fn blur_line(
+    src: &[u32],
+    radius: usize,
+    fields: [(u8, u8); 4],
+    mut put: impl FnMut(usize, u32),
+) {
+    let last = src.len() - 1;
+    let at = |idx: usize| {
+        let px = src[idx.min(last)];
+        fields.map(|(shift, bits)| (px >> shift) & ((1 << bits) - 1))
+    };
+    let window = 2 * radius as u32 + 1;
+    // Pixels left of the image are the same as the first pixel.
+    let mut sums = at(0).map(|ch| ch * (radius as u32 + 1));
+    for idx in 1..=radius {
+        let px = at(idx);
+        sums = core::array::from_fn(|i| sums[i] + px[i]);
+    }
+    for idx in 0..src.len() {
+        let px = fields.iter().zip(sums).fold(0, |px, (&(shift, _), sum)| {
+            px | (((sum + window / 2) / window) << shift)
+        });
+        put(idx, px);
+        let (add, sub) = (at(idx + radius + 1), at(idx.saturating_sub(radius)));
+        sums = core::array::from_fn(|i| sums[i] + add[i] - sub[i]);
+    }
+}
+
+#[test]
+fn test_box_blur() {
+    let shifts = RgbShifts::ARGB8888;
+    let mut data = vec![0x10203040; 12];
+    box_blur(&mut data, (4, 3), shifts, 2);
+    assert_eq!(vec![0x10203040; 12], data);
+
+    let shifts = RgbShifts::XRGB8888;
+    let mut data = vec![0, 0, 0, 0, 0, 0x00_F0_00_0F, 0, 0, 0];
+    box_blur(&mut data, (3, 3), shifts, 0);
+    assert_eq!(0x00_F0_00_0F, data[5]);
+    box_blur(&mut data, (3, 3), shifts, 1);
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0x00_1B_00_02, 0x00_35_00_03,
+        0, 0x00_1B_00_02, 0x00_35_00_03,
+        0, 0x00_1B_00_02, 0x00_35_00_03,
+    ], data);
+
+    // Channels which cross byte boundaries don’t bleed into each other.
+    let mut data = vec![0x3FF0_0000, 0];
+    box_blur(&mut data, (2, 1), RgbShifts::XRGB2101010, 1);
+    assert_eq!(vec![682 << 20, 341 << 20], data);
+    let mut data = vec![0xF800, 0];
+    box_blur(&mut data, (2, 1), RgbShifts::RGB565, 1);
+    assert_eq!(vec![21 << 11, 10 << 11], data);
+}
+
+/// Resamples visible part of an image using `image` crate’s resizing.
+///
+/// `imageops` is used rather than `fast_image_resize` since `image` already
+/// is an optional dependency of the crate.  It’s slower than SIMD-optimised
+/// resizing but the image is drawn on the root window only once.
+///
+/// `visible` is the `(x, y, width, height)` rectangle of the scaled image, in
+/// area’s coordinates, which is computed.  To avoid scaling parts of the image
+/// which aren’t visible, the source is first cropped to the region which maps
+/// onto the visible rectangle.
+///
+/// If all channels described by `rgb_shifts` are whole bytes, the pixels are
+/// resized as RGBA bytes.  Otherwise (e.g. in 30- or 16-bit formats), each
+/// channel is scaled to 16 bits before resizing and packed back afterwards.
+#[cfg(feature = "resize")]
+fn resample_filtered(
+    src: &[u32],
+    src_dim: (u16, u16),
+    placement: Placement,
+    visible: (u16, u16, u16, u16),
+    rgb_shifts: RgbShifts,
+    filter: Filter,
+) -> Vec<u32> {
+    use image::imageops::{self, FilterType};
+
+    /// Returns range of source pixels which map onto given range of pixels in
+    /// the scaled image.
+    fn span(
+        pos: u16,
+        len: u16,
+        offset: i64,
+        scaled: u64,
+        src: u16,
+    ) -> (u32, u32) {
+        let start = (i64::from(pos) - offset) as u64;
+        let end = start + u64::from(len);
+        let src = u64::from(src);
+        let first = start * src / scaled;
+        let last = (end * src).div_ceil(scaled).min(src);
+        (first as u32, (last - first) as u32)
+    }
+
+    let (x, y, width, height) = visible;
+    let (sx, sw) = span(x, width, placement.x, placement.width, src_dim.0);
+    let (sy, sh) = span(y, height, placement.y, placement.height, src_dim.1);
+
+    let filter = match filter {
+        Filter::Nearest => FilterType::Nearest,
+        Filter::Bilinear => FilterType::Triangle,
+        Filter::Lanczos3 => FilterType::Lanczos3,
+    };
+    let fields = rgb_shifts.fields();
+    if fields.iter().all(|&(shift, bits)| shift % 8 == 0 && bits % 8 == 0) {
+        let bytes: &[u8] = bytemuck::must_cast_slice(src);
+        let (src_w, src_h) = (u32::from(src_dim.0), u32::from(src_dim.1));
+        let img = image::ImageBuffer::<image::Rgba<u8>, _>::from_raw(
+            src_w, src_h, bytes,
+        )
+        .unwrap();
+        let view = imageops::crop_imm(&img, sx, sy, sw, sh);
+        let out = imageops::resize(&*view, width.into(), height.into(), filter);
+        return out
+            .chunks_exact(4)
+            .map(|px| u32::from_ne_bytes(px.try_into().unwrap()))
+            .collect();
+    }
+
+    // Scale channels to the full 16-bit range so that rounding and clamping
+    // done by the filters matches range of each channel.
+    let max = |bits: u8| (1u32 << bits) - 1;
+    let widen = |value: u32, bits| {
+        if bits == 0 { 0 } else { (value * 0xFFFF + max(bits) / 2) / max(bits) }
+    };
+    let narrow = |value: u16, bits| {
+        if bits == 0 {
+            0
+        } else {
+            (u32::from(value) * max(bits) + 0x7FFF) / 0xFFFF
+        }
+    };
+    let (sx, sw, stride) = (sx as usize, sw as usize, usize::from(src_dim.0));
+    let mut data = Vec::with_capacity(sw * sh as usize * 4);
+    for row in src.chunks_exact(stride).skip(sy as usize).take(sh as usize) {
+        for &px in &row[sx..sx + sw] {
+            data.extend(fields.map(|(shift, bits)| {
+                widen((px >> shift) & max(bits), bits) as u16
+            }));
+        }
+    }
+    let img = image::ImageBuffer::<image::Rgba<u16>, _>::from_raw(
+        sw as u32, sh, data,
+    )
+    .unwrap();
+    let out = imageops::resize(&img, width.into(), height.into(), filter);
+    out.chunks_exact(4)
+        .map(|px| {
+            fields.iter().zip(px).fold(0, |acc, (&(shift, bits), &value)| {
+                acc | (narrow(value, bits) << shift)
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_scale_mode_place() {
+    let place = |mode: ScaleMode, img| {
+        let Placement { x, y, width, height } =
+            mode.place(img, (1920, 1080), Gravity::Center);
+        (x, y, width, height)
+    };
+
+    assert_eq!((0, -180, 1920, 1440), place(ScaleMode::Fill, (4000, 3000)));
+    assert_eq!((240, 0, 1440, 1080), place(ScaleMode::Fit, (4000, 3000)));
+    assert_eq!((0, 0, 1920, 1080), place(ScaleMode::Stretch, (4000, 3000)));
+    assert_eq!(
+        (-1040, -960, 4000, 3000),
+        place(ScaleMode::Center, (4000, 3000))
+    );
+    assert_eq!((0, 0, 4000, 3000), place(ScaleMode::Tile, (4000, 3000)));
+
+    assert_eq!((-420, 0, 2760, 1080), place(ScaleMode::Fill, (460, 180)));
+    assert_eq!((0, 164, 1920, 752), place(ScaleMode::Fit, (460, 180)));
+    assert_eq!((730, 450, 460, 180), place(ScaleMode::Center, (460, 180)));
+}
+
+#[test]
+fn test_gravity() {
+    let place = |mode: ScaleMode, img, area, gravity| {
+        let Placement { x, y, .. } = mode.place(img, area, gravity);
+        (x, y)
+    };
+
+    // 3×2 image in 8×5 area leaves 5×3 pixels.  When centring, the odd pixel
+    // ends up on the right or bottom.
+    let want = [
+        (Gravity::NorthWest, (0, 0)),
+        (Gravity::North, (2, 0)),
+        (Gravity::NorthEast, (5, 0)),
+        (Gravity::West, (0, 1)),
+        (Gravity::Center, (2, 1)),
+        (Gravity::East, (5, 1)),
+        (Gravity::SouthWest, (0, 3)),
+        (Gravity::South, (2, 3)),
+        (Gravity::SouthEast, (5, 3)),
+    ];
+    for (gravity, want) in want {
+        let got = place(ScaleMode::Center, (3, 2), (8, 5), gravity);
+        assert_eq!(want, got, "{gravity:?}");
+    }
+
+    // Fit leaves space in one dimension only.  2×1 image is scaled to 5×3.
+    let fit = |gravity| place(ScaleMode::Fit, (2, 1), (5, 4), gravity);
+    assert_eq!((0, 0), fit(Gravity::NorthEast));
+    assert_eq!((0, 0), fit(Gravity::West));
+    assert_eq!((0, 1), fit(Gravity::SouthWest));
+
+    // Fill and Center crop the image on the side opposite to the gravity.
+    let fill = |gravity| place(ScaleMode::Fill, (1, 1), (3, 2), gravity);
+    assert_eq!((0, 0), fill(Gravity::North));
+    assert_eq!((0, 0), fill(Gravity::Center));
+    assert_eq!((0, -1), fill(Gravity::South));
+    let center = |gravity| place(ScaleMode::Center, (5, 5), (2, 2), gravity);
+    assert_eq!((-3, -3), center(Gravity::SouthEast));
+    assert_eq!((-1, -1), center(Gravity::Center));
+
+    // Stretch covers the area exactly and Tile ignores gravity.
+    let stretch = place(ScaleMode::Stretch, (3, 2), (8, 5), Gravity::SouthEast);
+    assert_eq!((0, 0), stretch);
+    let tile = place(ScaleMode::Tile, (3, 2), (8, 5), Gravity::SouthEast);
+    assert_eq!((0, 0), tile);
+}
+
+#[test]
+fn test_resample() {
+    const XRGB: RgbShifts = RgbShifts::XRGB8888;
+    let src = [1, 2, 3, 4];
+    let place = Placement { x: 0, y: 0, width: 4, height: 4 };
+    assert_eq!(
+        Some((0, 0, 4, 4, vec![
+            1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4
+        ])),
+        resample(&src, (2, 2), place, (4, 4), XRGB, Filter::Nearest)
+    );
+
+    let place = Placement { x: -2, y: 1, width: 4, height: 4 };
+    assert_eq!(
+        Some((0, 1, 2, 3, vec![2, 2, 2, 2, 4, 4])),
+        resample(&src, (2, 2), place, (4, 4), XRGB, Filter::Nearest)
+    );
+
+    let place = Placement { x: 4, y: 0, width: 4, height: 4 };
+    let got = resample(&src, (2, 2), place, (4, 4), XRGB, Filter::Nearest);
+    assert_eq!(None, got);
+}
+
+#[test]
+#[cfg(feature = "resize")]
+fn test_resample_filtered() {
+    const ARGB: RgbShifts = RgbShifts::ARGB8888;
+    // Uniform image stays uniform regardless of the filter.
+    let src = [0x10_20_30_40; 6];
+    let place = Placement { x: -1, y: 0, width: 6, height: 4 };
+    for filter in [Filter::Bilinear, Filter::Lanczos3] {
+        assert_eq!(
+            Some((0, 0, 4, 4, vec![0x10_20_30_40; 16])),
+            resample(&src, (3, 2), place, (4, 4), ARGB, filter)
+        );
+    }
+
+    // Bilinear filter blends neighbouring pixels.
+    let src = [0, 0xFF_FF_FF_FF];
+    let place = Placement { x: 0, y: 0, width: 4, height: 1 };
+    let (_, _, _, _, data) =
+        resample(&src, (2, 1), place, (4, 1), ARGB, Filter::Bilinear).unwrap();
+    assert_eq!(0, data[0]);
+    assert_eq!(0xFF_FF_FF_FF, data[3]);
+    assert!(data[1] != 0 && data[1] < data[2] && data[2] != 0xFF_FF_FF_FF);
+
+    // Channels of 10-bit pixels are blended separately without red spilling
+    // into bits of other channels.
+    const RGB30: RgbShifts = RgbShifts::XRGB2101010;
+    let src = [0x3FF0_0000, 0x000F_FC00];
+    let (_, _, _, _, data) =
+        resample(&src, (2, 1), place, (4, 1), RGB30, Filter::Bilinear).unwrap();
+    let channels = |px: u32| (px >> 20, (px >> 10) & 0x3FF, px & 0x3FF);
+    assert_eq!((0x3FF, 0, 0), channels(data[0]));
+    assert_eq!((0, 0x3FF, 0), channels(data[3]));
+    let (r1, g1, b1) = channels(data[1]);
+    let (r2, g2, b2) = channels(data[2]);
+    assert!(r1 > r2 && g1 < g2);
+    assert!(r1 + g1 == 0x3FF && r2 + g2 == 0x3FF);
+    assert_eq!((0, 0), (b1, b2));
+
+    // Uniform 10- and 16-bit images stay uniform as well.
+    let place = Placement { x: -1, y: 0, width: 6, height: 4 };
+    for (shifts, px) in [(RGB30, 0x1552_AFFF), (RgbShifts::RGB565, 0xA5F3)] {
+        for filter in [Filter::Bilinear, Filter::Lanczos3] {
+            assert_eq!(
+                Some((0, 0, 4, 4, vec![px; 16])),
+                resample(&[px; 6], (3, 2), place, (4, 4), shifts, filter)
+            );
+        }
+    }
+}
+
+
+/// Clockwise rotation applied to an image before it’s drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rotation {
+    /// Image is drawn as is.
+    #[default]
+    None,
+    /// Image is rotated by 90° clockwise.  Its width and height are swapped.
+    Cw90,
+    /// Image is rotated by 180°, i.e. turned upside down.
+    Cw180,
+    /// Image is rotated by 270° clockwise (i.e. 90° counter-clockwise).  Its
+    /// width and height are swapped.
+    Cw270,
+}
+
+impl Rotation {
+    /// Returns dimensions of an image of given dimensions after rotation.
+    pub fn rotate_dimensions(self, dim: (u16, u16)) -> (u16, u16) {
+        match self {
+            Self::None | Self::Cw180 => dim,
+            Self::Cw90 | Self::Cw270 => (dim.1, dim.0),
+        }
+    }
+
+    /// Returns rotation which undoes this one.
+    ///
+    /// ```
+    /// use setroot::img::Rotation;
+    ///
+    /// assert_eq!(Rotation::Cw270, Rotation::Cw90.inverse());
+    /// assert_eq!(Rotation::Cw180, Rotation::Cw180.inverse());
+    /// ```
+    pub fn inverse(self) -> Self {
+        match self {
+            Self::None => Self::None,
+            Self::Cw90 => Self::Cw270,
+            Self::Cw180 => Self::Cw180,
+            Self::Cw270 => Self::Cw90,
+        }
+    }
+}
+
+/// Rotates a `dim.0`×`dim.1` image in place.
+///
+/// Afterwards, `data` holds pixels of the rotated image whose dimensions are
+/// given by [`Rotation::rotate_dimensions`].  Rotations by 90° and 270° move
+/// pixels along cycles of the permutation in a single pass.  To move each
+/// cycle once without remembering which pixels have been visited, a cycle is
+/// moved only when reached from its smallest index which is checked by
+/// walking the cycle.  This trades extra index computations for not needing
+/// any additional memory.
+pub(crate) fn rotate_in_place(
+    data: &mut [u32],
+    dim: (u16, u16),
+    rotation: Rotation,
+) {
+    let (width, height) = (usize::from(dim.0), usize::from(dim.1));
+    match rotation {
+        Rotation::None => (),
+        Rotation::Cw180 => data.reverse(),
+        // Pixel at (x, y) moves to (height - 1 - y, x) or (y, width - 1 - x)
+        // respectively in the rotated image which is `height` pixels wide.
+        Rotation::Cw90 => permute_in_place(data, |idx| {
+            let (x, y) = (idx % width, idx / width);
+            x * height + height - 1 - y
+        }),
+        Rotation::Cw270 => permute_in_place(data, |idx| {
+            let (x, y) = (idx % width, idx / width);
+            (width - 1 - x) * height + y
+        }),
+    }
+}
+
+/// Moves each element of `data` from index `idx` to index `dest(idx)`.
+///
+/// `dest` must be a permutation of indices of `data`.
+fn permute_in_place(data: &mut [u32], dest: impl Fn(usize) -> usize) {
+    for start in 0..data.len() {
+        let mut idx = dest(start);
+        while idx > start {
+            idx = dest(idx);
+        }
+        if idx < start {
+            // The cycle has been moved when reached from a smaller index.
+            continue;
+        }
+        let mut pixel = data[start];
+        loop {
+            idx = dest(idx);
+            core::mem::swap(&mut pixel, &mut data[idx]);
+            if idx == start {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rotate_in_place() {
+    let rotate = |src: &[u32], dim, rotation| {
+        let mut data = src.to_vec();
+        rotate_in_place(&mut data, dim, rotation);
+        data
+    };
+    // 3×2 image:
+    //   1 2 3
+    //   4 5 6
+    let src = [1, 2, 3, 4, 5, 6];
+    assert_eq!(src.to_vec(), rotate(&src, (3, 2), Rotation::None));
+    assert_eq!(vec![4, 1, 5, 2, 6, 3], rotate(&src, (3, 2), Rotation::Cw90));
+    assert_eq!(vec![6, 5, 4, 3, 2, 1], rotate(&src, (3, 2), Rotation::Cw180));
+    assert_eq!(vec![3, 6, 2, 5, 1, 4], rotate(&src, (3, 2), Rotation::Cw270));
+    assert_eq!((2, 3), Rotation::Cw90.rotate_dimensions((3, 2)));
+    assert_eq!((3, 2), Rotation::Cw180.rotate_dimensions((3, 2)));
+
+    // Rotating a larger image four times yields the original.
+    let src = (0..7 * 5).collect::<Vec<u32>>();
+    let mut data = src.clone();
+    let mut dim = (7, 5);
+    for _ in 0..4 {
+        rotate_in_place(&mut data, dim, Rotation::Cw90);
+        dim = Rotation::Cw90.rotate_dimensions(dim);
+    }
+    assert_eq!(src, data);
+    rotate_in_place(&mut data, (7, 5), Rotation::Cw270);
+    rotate_in_place(&mut data, (5, 7), Rotation::Cw90);
+    assert_eq!(src, data);
+
+    // Single row turns into a single column.
+    assert_eq!(vec![1, 2, 3], rotate(&[1, 2, 3], (3, 1), Rotation::Cw90));
+    assert_eq!(vec![3, 2, 1], rotate(&[1, 2, 3], (3, 1), Rotation::Cw270));
+}
+
+/// Constructs a tile for mirrored tiling out of a `dim.0`×`dim.1` image.
+///
+/// The result is twice as wide and twice as tall as the image and consists of
+/// the image, its horizontal mirror image to its right and vertical mirror
+/// images of those two underneath.  Repeating such a tile yields a pattern in
+/// which edges of adjacent copies of the image meet continuously.  Returns an
+/// error if the doubled dimensions don’t fit `u16`.
+pub(crate) fn mirror_tile(
+    src: &[u32],
+    dim: (u16, u16),
+) -> Result<((u16, u16), Vec<u32>), err::ImageTooLarge> {
+    let (width, height) = (u32::from(dim.0) * 2, u32::from(dim.1) * 2);
+    let new_dim = new_dimensions((width, height))?;
+    let mut data = Vec::with_capacity(width as usize * height as usize);
+    if dim.0 != 0 {
+        let rows = src.chunks_exact(usize::from(dim.0));
+        for row in rows.clone().chain(rows.rev()) {
+            data.extend_from_slice(row);
+            data.extend(row.iter().rev());
+        }
+    }
+    Ok((new_dim, data))
+}
+
+#[test]
+fn test_mirror_tile() {
+    // 2×2 image:
+    //   1 2
+    //   3 4
+    let ((width, height), data) = mirror_tile(&[1, 2, 3, 4], (2, 2)).unwrap();
+    assert_eq!((4, 4), (width, height));
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        1, 2, 2, 1,
+        3, 4, 4, 3,
+        3, 4, 4, 3,
+        1, 2, 2, 1,
+    ], data);
+
+    assert_eq!(Ok(((0, 2), vec![])), mirror_tile(&[], (0, 1)));
+    let err = mirror_tile(&[], (40_000, 0)).unwrap_err();
+    assert_eq!(err::ImageTooLarge(80_000, 0), err);
+}
+
+
+/// Direction in which colour changes in a gradient.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GradientDirection {
+    /// Colour changes from top to bottom; each row is a solid colour.
+    Vertical,
+    /// Colour changes from left to right; each column is a solid colour.
+    Horizontal,
+}
+
+/// Dithering applied when quantising computed colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Dither {
+    /// Colours are rounded to the nearest 8-bit value.  Smooth gradients may
+    /// show visible bands.
+    #[default]
+    None,
+    /// Ordered dithering using 8×8 Bayer matrix.  The quantisation error is
+    /// spread in a regular pattern which hides banding in gradients.
+    Ordered,
+}
+
+/// Linearly interpolates between two colours.
+///
+/// Returns colour at position `pos` of a gradient which is `len` pixels long.
+/// First pixel has the `start` colour while the last one has the `end` colour.
+///
+/// The interpolation is done in sRGB space (i.e. on gamma-encoded values)
+/// which is fast but not physically accurate.  Mid-tones of gradients between
+/// saturated colours may appear darker than expected.
+pub(crate) fn interpolate(
+    start: (u8, u8, u8),
+    end: (u8, u8, u8),
+    pos: u16,
+    len: u16,
+) -> (u8, u8, u8) {
+    let den = i32::from(len.max(2) - 1);
+    let pos = i32::from(pos);
+    let lerp = |a: u8, b: u8| {
+        let (a, b) = (i32::from(a), i32::from(b));
+        (a + ((b - a) * pos * 2 + den).div_euclid(den * 2)) as u8
+    };
+    (lerp(start.0, end.0), lerp(start.1, end.1), lerp(start.2, end.2))
+}
+
+/// Linearly interpolates between two colours applying ordered dithering.
+///
+/// Works like [`interpolate`] except that rather than rounding the colour to
+/// the nearest 8-bit value, fractional part of the components is compared
+/// against a threshold from 8×8 Bayer matrix picked based on pixel’s `(x, y)`
+/// coordinates.
+pub(crate) fn interpolate_dithered(
+    start: (u8, u8, u8),
+    end: (u8, u8, u8),
+    pos: u16,
+    len: u16,
+    (x, y): (u16, u16),
+) -> (u8, u8, u8) {
+    #[rustfmt::skip]
+    const BAYER: [[u8; 8]; 8] = [
+        [ 0, 32,  8, 40,  2, 34, 10, 42],
+        [48, 16, 56, 24, 50, 18, 58, 26],
+        [12, 44,  4, 36, 14, 46,  6, 38],
+        [60, 28, 52, 20, 62, 30, 54, 22],
+        [ 3, 35, 11, 43,  1, 33,  9, 41],
+        [51, 19, 59, 27, 49, 17, 57, 25],
+        [15, 47,  7, 39, 13, 45,  5, 37],
+        [63, 31, 55, 23, 61, 29, 53, 21],
+    ];
+    let threshold = i32::from(BAYER[usize::from(y % 8)][usize::from(x % 8)]);
+    let den = i32::from(len.max(2) - 1);
+    let pos = i32::from(pos);
+    let lerp = |a: u8, b: u8| {
+        let (a, b) = (i32::from(a), i32::from(b));
+        // Value in 1/64ths of a step.
+        let value = a * 64 + ((b - a) * pos * 64).div_euclid(den);
+        ((value + threshold) / 64) as u8
+    };
+    (lerp(start.0, end.0), lerp(start.1, end.1), lerp(start.2, end.2))
+}
+
+#[test]
+fn test_interpolate() {
+    let (start, end) = ((0, 255, 10), (255, 0, 10));
+    assert_eq!(start, interpolate(start, end, 0, 5));
+    assert_eq!((64, 191, 10), interpolate(start, end, 1, 5));
+    assert_eq!((128, 128, 10), interpolate(start, end, 2, 5));
+    assert_eq!((191, 64, 10), interpolate(start, end, 3, 5));
+    assert_eq!(end, interpolate(start, end, 4, 5));
+    assert_eq!(start, interpolate(start, end, 0, 1));
+}
+
+#[test]
+fn test_interpolate_dithered() {
+    let (start, end) = ((0, 255, 10), (255, 0, 10));
+    for (x, y) in [(0, 0), (7, 7), (3, 5)] {
+        assert_eq!(start, interpolate_dithered(start, end, 0, 5, (x, y)));
+        assert_eq!(end, interpolate_dithered(start, end, 4, 5, (x, y)));
+    }
+
+    // Halfway between 10 and 11 half of the pixels in a tile round up.
+    let (start, end) = ((10, 10, 10), (11, 11, 11));
+    let count = (0..8)
+        .flat_map(|y| (0..8).map(move |x| (x, y)))
+        .filter(|&xy| interpolate_dithered(start, end, 1, 3, xy).0 == 11)
+        .count();
+    assert_eq!(32, count);
+}
+
+
+#[derive(Clone)]
+struct InnerImage<'a, S: Clone> {
+    dimensions: (u16, u16),
+    data: Cow<'a, [S]>,
+}
+
+impl<'a, S: Clone> InnerImage<'a, S> {
+    pub fn new(
+        width: u32,
+        height: u32,
+        data: Cow<'a, [S]>,
+        channels: usize,
+    ) -> Result<Self, Error> {
+        let (width, height) = new_dimensions((width, height))?;
+        if usize::from(width) * usize::from(height) * channels == data.len() {
             Ok(Self { dimensions: (width, height), data })
         } else {
             let len = data.len() * core::mem::size_of::<S>();
@@ -212,10 +1838,148 @@ impl<'a, S: Clone> InnerImage<'a, S> {
 #[as_ref(Vec<u32>, [u32])]
 pub struct XBuffer(Vec<u32>);
 
+impl XBuffer {
+    /// Converts an image into a newly allocated buffer using
+    /// [`IntoXBuffer::into_x_buffer_in`].
+    fn from_image<'a>(
+        img: impl IntoXBuffer<'a>,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self> {
+        let mut data = Vec::new();
+        img.into_x_buffer_in(rgb_shifts, &mut data)?;
+        Ok(Self(data))
+    }
+}
+
 impl AsRef<[u8]> for XBuffer {
     fn as_ref(&self) -> &[u8] { bytemuck::must_cast_slice(self.0.as_slice()) }
 }
 
+
+/// An image converted into format supported by the X display server ahead of
+/// time.
+///
+/// Converting an image is often the most expensive part of drawing it.
+/// A prepared image holds the result of the conversion such that an image
+/// drawn repeatedly (e.g. in a slideshow which cycles through a handful of
+/// images) needs to be converted only once.  Use
+/// [`crate::RootPixmap::prepare`] to construct it and
+/// [`crate::RootPixmap::put_prepared`] to draw it.  `&PreparedImage`
+/// implements [`IntoXBuffer`] so it can be passed to other drawing methods as
+/// well.
+///
+/// The conversion depends on the pixel format of the display.  The image is
+/// therefore tied to the display (and screen) it was prepared for.  Drawing it
+/// on a surface with different [`RgbShifts`] works but requires converting the
+/// pixels again which loses any advantage and may lose precision.
+///
+/// # Example
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// # use setroot::img::{MemoryCanvas, PreparedImage, RgbImage, RgbShifts};
+/// let data: &[u8] = &[1, 2, 3];
+/// let img = RgbImage::new(1, 1, data.into()).unwrap();
+/// let prepared = PreparedImage::new(img, RgbShifts::XRGB8888).unwrap();
+///
+/// let canvas = MemoryCanvas::new(2, 1, RgbShifts::XRGB8888);
+/// canvas.put_image(0, 0, &prepared).unwrap();
+/// canvas.put_image(1, 0, &prepared).unwrap();
+/// assert_eq!(&[0x010203, 0x010203], &*canvas.pixels());
+/// ```
+#[derive(Clone, Debug)]
+pub struct PreparedImage {
+    dimensions: (u16, u16),
+    rgb_shifts: RgbShifts,
+    data: Vec<u32>,
+}
+
+impl PreparedImage {
+    /// Converts an image into format described by given RGB shifts.
+    ///
+    /// Returns an error if the dimensions of the image are too large or the
+    /// conversion fails.
+    pub fn new<'a>(
+        img: impl IntoXBuffer<'a>,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self> {
+        let dimensions = img.dimensions()?;
+        let mut data = Vec::new();
+        img.into_x_buffer_in(rgb_shifts, &mut data)?;
+        let (width, height) = dimensions;
+        if usize::from(width) * usize::from(height) != data.len() {
+            return Err(Error::BadBufferSize(data.len() * 4, width, height));
+        }
+        Ok(Self { dimensions, rgb_shifts, data })
+    }
+
+    /// Returns dimensions of the image.
+    pub fn dimensions(&self) -> (u16, u16) { self.dimensions }
+
+    /// Returns RGB shifts the image has been converted for.
+    pub fn rgb_shifts(&self) -> RgbShifts { self.rgb_shifts }
+
+    /// Returns pixels of the image in format described by
+    /// [`Self::rgb_shifts`].
+    pub fn pixels(&self) -> &[u32] { &self.data }
+}
+
+impl<'a> IntoXBuffer<'a> for &'a PreparedImage {
+    type Buffer = Cow<'a, [u8]>;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        Ok(self.dimensions)
+    }
+
+    /// Returns the prepared pixels without copying them if `rgb_shifts` match
+    /// those the image was prepared for.  Otherwise, converts the pixels into
+    /// the requested format.
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        if rgb_shifts == self.rgb_shifts {
+            return Ok(Cow::Borrowed(bytemuck::must_cast_slice(&self.data)));
+        }
+        let mut data = self.data.clone();
+        rgb_shifts.repack_buffer(self.rgb_shifts, &mut data);
+        Ok(Cow::Owned(data.into_iter().flat_map(u32::to_ne_bytes).collect()))
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        out.clear();
+        out.extend_from_slice(&self.data);
+        rgb_shifts.repack_buffer(self.rgb_shifts, out);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_prepared_image() {
+    let data: &[u8] = &[1, 2, 3, 4, 5, 6];
+    let img = RgbImage::new(2, 1, data.into()).unwrap();
+    let prepared = PreparedImage::new(img, RgbShifts::XRGB8888).unwrap();
+    assert_eq!((2, 1), prepared.dimensions());
+    assert_eq!(&[0x010203, 0x040506], prepared.pixels());
+
+    let buffer = (&prepared).into_x_buffer(RgbShifts::XRGB8888).unwrap();
+    assert!(matches!(buffer, Cow::Borrowed(_)));
+
+    // Different format requires conversion.
+    let buffer = (&prepared).into_x_buffer(RgbShifts::XBGR8888).unwrap();
+    let (chunks, _) = buffer.as_chunks();
+    let pixels = chunks.iter().copied().map(u32::from_ne_bytes);
+    assert_eq!(vec![0x030201, 0x060504], pixels.collect::<Vec<_>>());
+
+    let mut buffer = vec![42];
+    (&prepared).into_x_buffer_in(RgbShifts::XBGR8888, &mut buffer).unwrap();
+    assert_eq!(vec![0x030201, 0x060504], buffer);
+}
+
 // https://danielkeep.github.io/tlborm/book/blk-counting.html
 macro_rules! replace_expr {
     ($_t:tt $sub:expr) => {
@@ -249,10 +2013,24 @@ macro_rules! make_image_type {
         impl<'a, S: Subpixel> IntoXBuffer<'a> for $Image<'a, S> {
             type Buffer = XBuffer;
             fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> { Ok(self.0.dimensions) }
-            fn into_x_buffer(self, $rgb_shifts: RgbShifts) -> crate::Result<Self::Buffer> {
+            fn into_x_buffer(self, rgb_shifts: RgbShifts) -> crate::Result<Self::Buffer> {
+                XBuffer::from_image(self, rgb_shifts)
+            }
+            fn into_x_buffer_in(self, $rgb_shifts: RgbShifts, out: &mut Vec<u32>) -> crate::Result {
                 let (chunks, remainder) = self.0.data.as_chunks();
                 assert_eq!(0, remainder.len());
-                Ok(XBuffer(chunks.iter().map(|&[$($ch),*]| $body).collect()))
+                out.clear();
+                #[cfg(not(feature = "rayon"))]
+                out.extend(chunks.iter().map(|&[$($ch),*]| $body));
+                #[cfg(feature = "rayon")]
+                rayon::iter::ParallelExtend::par_extend(
+                    out,
+                    rayon::iter::ParallelIterator::map(
+                        rayon::iter::IntoParallelIterator::into_par_iter(chunks),
+                        |&[$($ch),*]| $body,
+                    ),
+                );
+                Ok(())
             }
         }
     }
@@ -274,7 +2052,7 @@ make_image_type! {
     /// assert_eq!(Ok((2, 1)), img.dimensions());
     ///
     /// // Convert to X Buffer.
-    /// let shifts = RgbShifts { r: 16, g: 8, b: 0 };
+    /// let shifts = RgbShifts::XRGB8888;
     /// let xbuf = img.into_x_buffer(shifts).unwrap();
     /// let xbuf: &[u8] = xbuf.as_ref();
     ///
@@ -289,7 +2067,9 @@ make_image_type! {
 make_image_type! {
     /// An image in RGBA format and sRGB colour space.
     ///
-    /// Alpha channel is ignored when converting to X-compatible image buffer.
+    /// Alpha channel is ignored when converting to X-compatible image buffer
+    /// unless the display server’s pixel format includes alpha channel (see
+    /// [`RgbShifts::a`]).
     ///
     /// # Example
     ///
@@ -304,79 +2084,1401 @@ make_image_type! {
     /// assert_eq!(Ok((1, 1)), img.dimensions());
     ///
     /// // Convert to X Buffer.
-    /// let shifts = RgbShifts { r: 16, g: 8, b: 0 };
+    /// let shifts = RgbShifts::XRGB8888;
     /// let xbuf = img.into_x_buffer(shifts).unwrap();
     /// let xbuf: &[u8] = xbuf.as_ref();
     ///
     /// let colour = u32::from_ne_bytes(xbuf.try_into().unwrap());
     /// assert_eq!(0x00102030, colour);
     /// ```
-    RgbaImage; |[r, g, b, _alpha], rgb_shifts| rgb_shifts.from_rgb(r, g, b)
+    RgbaImage; |[r, g, b, a], rgb_shifts| rgb_shifts.from_rgba(r, g, b, a)
 }
 make_image_type! {
-    /// An greyscale image in sRGB colour space.
+    /// An image in BGR format and sRGB colour space.
     ///
     /// # Example
     ///
     /// ```
-    /// # use setroot::img::{LumaImage, RgbShifts};
+    /// # use setroot::img::{BgrImage, RgbShifts};
     /// use setroot::img::IntoXBuffer;
     ///
-    /// // Construct 2×1 greyscale image with f32 subpixels.
-    /// let data: &[f32] = &[0.5, 0.75][..];
-    /// let img = LumaImage::new(2, 1, data.into()).unwrap();
+    /// // Construct 2×1 BGR image.
+    /// let data: &[u8] = &[3, 2, 1, 6, 5, 4][..];
+    /// let img = BgrImage::new(2, 1, data.into()).unwrap();
     ///
     /// assert_eq!(Ok((2, 1)), img.dimensions());
     ///
     /// // Convert to X Buffer.
-    /// let shifts = RgbShifts { r: 16, g: 8, b: 0 };
+    /// let shifts = RgbShifts::XRGB8888;
     /// let xbuf = img.into_x_buffer(shifts).unwrap();
     /// let xbuf: &[u8] = xbuf.as_ref();
     ///
-    /// assert_eq!(&[128, 128, 128, 128, 191, 191, 191, 191], xbuf);
+    /// if cfg!(target_endian = "little") {
+    ///     assert_eq!(&[3, 2, 1, 0, 6, 5, 4, 0], xbuf);
+    /// } else  {
+    ///     assert_eq!(&[0, 1, 2, 3, 0, 4, 5, 6], xbuf);
+    /// }
     /// ```
-    LumaImage; |[y], rgb_shifts| rgb_shifts.from_luma(y)
+    BgrImage; |[b, g, r], rgb_shifts| rgb_shifts.from_rgb(r, g, b)
 }
 make_image_type! {
-    /// An greyscale image with alpha channel in sRGB colour space.
+    /// An image in BGRA format and sRGB colour space.
     ///
-    /// Alpha channel is ignored when converting to X-compatible image buffer.
+    /// Alpha channel is ignored when converting to X-compatible image buffer
+    /// unless the display server’s pixel format includes alpha channel (see
+    /// [`RgbShifts::a`]).
     ///
     /// # Example
     ///
     /// ```
-    /// # use setroot::img::{LumaAImage, RgbShifts};
+    /// # use setroot::img::{BgraImage, RgbShifts};
     /// use setroot::img::IntoXBuffer;
     ///
-    /// // Construct 2×1 greyscale image.
-    /// let data: &[u8] = &[10, 255, 20, 255][..];
-    /// let img = LumaAImage::new(2, 1, data.into()).unwrap();
+    /// // Construct 1×1 BGRA image.
+    /// let data: &[u8] = &[0x30, 0x20, 0x10, 255][..];
+    /// let img = BgraImage::new(1, 1, data.into()).unwrap();
     ///
-    /// assert_eq!(Ok((2, 1)), img.dimensions());
+    /// assert_eq!(Ok((1, 1)), img.dimensions());
     ///
     /// // Convert to X Buffer.
-    /// let shifts = RgbShifts { r: 16, g: 8, b: 0 };
+    /// let shifts = RgbShifts::XRGB8888;
     /// let xbuf = img.into_x_buffer(shifts).unwrap();
     /// let xbuf: &[u8] = xbuf.as_ref();
     ///
-    /// assert_eq!(&[10, 10, 10, 10, 20, 20, 20, 20], xbuf);
+    /// let colour = u32::from_ne_bytes(xbuf.try_into().unwrap());
+    /// assert_eq!(0x00102030, colour);
     /// ```
-    LumaAImage; |[y, _alpha], rgb_shifts| rgb_shifts.from_luma(y)
-}
-
-#[test]
-fn test_buffer_size_mismatch() {
-    // 2×2 image with 4 pixels = 12 bytes
-    let data: [u8; 13] = [0; 13];
-    let res = RgbImage::new(2, 2, (&data[..11]).into());
-    assert!(matches!(res, Err(Error::BadBufferSize(11, 2, 2))));
-    let res = RgbImage::new(2, 2, (&data[..13]).into());
-    assert!(matches!(res, Err(Error::BadBufferSize(13, 2, 2))));
-    RgbImage::new(2, 2, (&data[..12]).into()).unwrap();
+    BgraImage; |[b, g, r, a], rgb_shifts| rgb_shifts.from_rgba(r, g, b, a)
 }
-
-#[cfg(feature = "image")]
-impl IntoXBuffer<'static> for image::DynamicImage {
+make_image_type! {
+    /// An greyscale image in sRGB colour space.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{LumaImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 2×1 greyscale image with f32 subpixels.
+    /// let data: &[f32] = &[0.5, 0.75][..];
+    /// let img = LumaImage::new(2, 1, data.into()).unwrap();
+    ///
+    /// assert_eq!(Ok((2, 1)), img.dimensions());
+    ///
+    /// // Convert to X Buffer.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// let xbuf: &[u8] = xbuf.as_ref();
+    ///
+    /// assert_eq!(&[128, 128, 128, 128, 191, 191, 191, 191], xbuf);
+    /// ```
+    LumaImage; |[y], rgb_shifts| rgb_shifts.from_luma(y)
+}
+make_image_type! {
+    /// An greyscale image with alpha channel in sRGB colour space.
+    ///
+    /// Alpha channel is ignored when converting to X-compatible image buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{LumaAImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 2×1 greyscale image.
+    /// let data: &[u8] = &[10, 255, 20, 255][..];
+    /// let img = LumaAImage::new(2, 1, data.into()).unwrap();
+    ///
+    /// assert_eq!(Ok((2, 1)), img.dimensions());
+    ///
+    /// // Convert to X Buffer.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// let xbuf: &[u8] = xbuf.as_ref();
+    ///
+    /// assert_eq!(&[10, 10, 10, 10, 20, 20, 20, 20], xbuf);
+    /// ```
+    LumaAImage; |[y, _alpha], rgb_shifts| rgb_shifts.from_luma(y)
+}
+
+/// An image in packed 16-bit RGB565 format and sRGB colour space.
+///
+/// Each pixel is a single `u16` with red component stored in the five most
+/// significant bits, green in the middle six bits and blue in the five least
+/// significant bits.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{Rgb565Image, RgbShifts};
+/// use setroot::img::IntoXBuffer;
+///
+/// // Construct 4×1 RGB565 image.
+/// let data: &[u16] = &[0xF800, 0x07E0, 0x001F, 0x8410][..];
+/// let img = Rgb565Image::new(4, 1, data.into()).unwrap();
+///
+/// assert_eq!(Ok((4, 1)), img.dimensions());
+///
+/// // Convert to X Buffer.
+/// let shifts = RgbShifts::XRGB8888;
+/// let xbuf = img.into_x_buffer(shifts).unwrap();
+/// assert_eq!(&[0xFF_00_00, 0x00_FF_00, 0x00_00_FF, 0x84_82_84], &xbuf[..]);
+/// ```
+#[derive(Clone)]
+pub struct Rgb565Image<'a>(InnerImage<'a, u16>);
+
+impl<'a> Rgb565Image<'a> {
+    /// Constructs a new image with given data.
+    pub fn new(
+        width: u32,
+        height: u32,
+        data: Cow<'a, [u16]>,
+    ) -> Result<Self, Error> {
+        InnerImage::new(width, height, data, 1).map(Self)
+    }
+}
+
+impl<'a> IntoXBuffer<'a> for Rgb565Image<'a> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        Ok(self.0.dimensions)
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        // Expand the components to eight bits replicating most significant
+        // bits in the low bits so that full intensity maps to 255.
+        let expand5 = |v: u16| ((v << 3) | (v >> 2)) as u8;
+        let expand6 = |v: u16| ((v << 2) | (v >> 4)) as u8;
+        out.clear();
+        out.extend(self.0.data.iter().map(|&px| {
+            rgb_shifts.from_rgb(
+                expand5(px >> 11),
+                expand6((px >> 5) & 0x3F),
+                expand5(px & 0x1F),
+            )
+        }));
+        Ok(())
+    }
+}
+
+/// An indexed image in sRGB colour space.
+///
+/// Each pixel is an index into a palette of colours.  Converting the image
+/// into X-compatible image buffer fails with [`Error::PaletteIndexOutOfRange`]
+/// if any of the indices doesn’t refer to an entry in the palette.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{IndexedImage, RgbShifts};
+/// use setroot::img::IntoXBuffer;
+///
+/// // Construct 3×1 indexed image.
+/// let palette = &[(255, 0, 0), (0, 0, 255)];
+/// let data: &[u8] = &[1, 0, 1][..];
+/// let img = IndexedImage::new(3, 1, data.into(), palette).unwrap();
+///
+/// assert_eq!(Ok((3, 1)), img.dimensions());
+///
+/// // Convert to X Buffer.
+/// let shifts = RgbShifts::XRGB8888;
+/// let xbuf = img.clone().into_x_buffer(shifts).unwrap();
+/// assert_eq!(&[0x00_00_FF, 0xFF_00_00, 0x00_00_FF], &xbuf[..]);
+///
+/// // Indices must be within the palette.
+/// let img = IndexedImage::new(1, 1, (&[2][..]).into(), palette).unwrap();
+/// assert!(matches!(
+///     img.into_x_buffer(shifts),
+///     Err(setroot::Error::PaletteIndexOutOfRange(2))
+/// ));
+/// ```
+#[derive(Clone)]
+pub struct IndexedImage<'a> {
+    img: InnerImage<'a, u8>,
+    palette: &'a [(u8, u8, u8)],
+}
+
+impl<'a> IndexedImage<'a> {
+    /// Constructs a new image with given data and palette.
+    pub fn new(
+        width: u32,
+        height: u32,
+        data: Cow<'a, [u8]>,
+        palette: &'a [(u8, u8, u8)],
+    ) -> Result<Self, Error> {
+        let img = InnerImage::new(width, height, data, 1)?;
+        Ok(Self { img, palette })
+    }
+}
+
+impl<'a> IntoXBuffer<'a> for IndexedImage<'a> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        Ok(self.img.dimensions)
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let palette = self
+            .palette
+            .iter()
+            .map(|&(r, g, b)| rgb_shifts.from_rgb(r, g, b))
+            .collect::<Vec<_>>();
+        out.clear();
+        out.reserve(self.img.data.len());
+        for &idx in self.img.data.iter() {
+            let colour = palette.get(usize::from(idx));
+            out.push(*colour.ok_or(Error::PaletteIndexOutOfRange(idx))?);
+        }
+        Ok(())
+    }
+}
+
+/// An image in planar RGB format and sRGB colour space.
+///
+/// Rather than interleaving the components of each pixel, the image stores
+/// red, green and blue components in three separate planes.  Each plane holds
+/// a single component of all pixels of the image.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{PlanarRgbImage, RgbShifts};
+/// use setroot::img::IntoXBuffer;
+///
+/// // Construct 2×1 planar RGB image.
+/// let (r, g, b): (&[u8], &[u8], &[u8]) = (&[1, 4], &[2, 5], &[3, 6]);
+/// let img = PlanarRgbImage::new(2, 1, r.into(), g.into(), b.into()).unwrap();
+///
+/// assert_eq!(Ok((2, 1)), img.dimensions());
+///
+/// // Convert to X Buffer.
+/// let shifts = RgbShifts::XRGB8888;
+/// let xbuf = img.into_x_buffer(shifts).unwrap();
+/// assert_eq!(&[0x01_02_03, 0x04_05_06], &xbuf[..]);
+///
+/// // All planes must match image dimensions.
+/// let b: &[u8] = &[3];
+/// let res = PlanarRgbImage::new(2, 1, r.into(), g.into(), b.into());
+/// assert!(matches!(res, Err(setroot::Error::BadBufferSize(1, 2, 1))));
+/// ```
+#[derive(Clone)]
+pub struct PlanarRgbImage<'a, S: Clone> {
+    r: InnerImage<'a, S>,
+    g: InnerImage<'a, S>,
+    b: InnerImage<'a, S>,
+}
+
+impl<'a, S: Clone> PlanarRgbImage<'a, S> {
+    /// Constructs a new image with given planes.
+    pub fn new(
+        width: u32,
+        height: u32,
+        r: Cow<'a, [S]>,
+        g: Cow<'a, [S]>,
+        b: Cow<'a, [S]>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            r: InnerImage::new(width, height, r, 1)?,
+            g: InnerImage::new(width, height, g, 1)?,
+            b: InnerImage::new(width, height, b, 1)?,
+        })
+    }
+}
+
+impl<'a, S: Subpixel> IntoXBuffer<'a> for PlanarRgbImage<'a, S> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        Ok(self.r.dimensions)
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let pixels = self.r.data.iter().zip(self.g.data.iter());
+        let pixels = pixels.zip(self.b.data.iter());
+        out.clear();
+        out.extend(pixels.map(|((&r, &g), &b)| rgb_shifts.from_rgb(r, g, b)));
+        Ok(())
+    }
+}
+
+/// A borrowed 8-bit RGBA image in sRGB colour space whose rows may be padded.
+///
+/// Each row of the image starts `stride_bytes` bytes after the start of the
+/// previous one.  Only the first `width * 4` bytes of each row hold pixel data.
+/// The remaining bytes are ignored.  This allows using framebuffers with
+/// padded rows without repacking them first.  Just like with [`RgbaImage`],
+/// alpha channel is copied into the X buffer if the visual has one.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{RawRgba, RgbShifts};
+/// use setroot::img::IntoXBuffer;
+///
+/// // Construct 1×2 image with rows padded to eight bytes.
+/// let data: &[u8] = &[1, 2, 3, 255, 0, 0, 0, 0, 4, 5, 6, 255];
+/// let img = RawRgba::new(1, 2, 8, data).unwrap();
+///
+/// assert_eq!(Ok((1, 2)), img.dimensions());
+///
+/// // Convert to X Buffer.
+/// let shifts = RgbShifts::XRGB8888;
+/// let xbuf = img.into_x_buffer(shifts).unwrap();
+/// assert_eq!(&[0x01_02_03, 0x04_05_06], &xbuf[..]);
+///
+/// // Stride must fit entire row and the buffer all the rows.
+/// let res = RawRgba::new(1, 2, 2, data);
+/// assert!(matches!(res, Err(setroot::Error::BadBufferSize(12, 1, 2))));
+/// let res = RawRgba::new(1, 2, 16, data);
+/// assert!(matches!(res, Err(setroot::Error::BadBufferSize(12, 1, 2))));
+/// ```
+#[derive(Clone, Copy)]
+pub struct RawRgba<'a> {
+    data: &'a [u8],
+    width: u16,
+    height: u16,
+    stride_bytes: usize,
+}
+
+impl<'a> RawRgba<'a> {
+    /// Constructs a new image with given data and row stride in bytes.
+    ///
+    /// Returns an error if the stride is smaller than `width * 4` or if the
+    /// data is too short to hold all the rows.  The last row doesn’t need to be
+    /// padded.
+    pub fn new(
+        width: u32,
+        height: u32,
+        stride_bytes: usize,
+        data: &'a [u8],
+    ) -> Result<Self, Error> {
+        let (width, height) = new_dimensions((width, height))?;
+        let row_len = usize::from(width) * 4;
+        let len = match usize::from(height).checked_sub(1) {
+            None => 0,
+            Some(rows) => stride_bytes
+                .checked_mul(rows)
+                .and_then(|len| len.checked_add(row_len))
+                .unwrap_or(usize::MAX),
+        };
+        if stride_bytes >= row_len && data.len() >= len {
+            Ok(Self { data, width, height, stride_bytes })
+        } else {
+            Err(Error::BadBufferSize(data.len(), width, height))
+        }
+    }
+}
+
+/// Returns whether packing 8-bit RGBA components with given shifts keeps the
+/// bytes in red, green, blue and alpha order, i.e. whether RGBA data can be
+/// sent to the X display server as is.
+///
+/// If the format has no alpha channel, the fourth byte is ignored by the
+/// server so it may hold anything.
+fn is_rgba_byte_order(rgb_shifts: RgbShifts) -> bool {
+    let [r, g, b, a] = rgb_shifts.from_rgba(1u8, 2, 3, 4).to_ne_bytes();
+    [r, g, b] == [1, 2, 3] && (a == 0 || a == 4)
+}
+
+impl<'a> IntoXBuffer<'a> for RawRgba<'a> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        Ok((self.width, self.height))
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let row_len = usize::from(self.width) * 4;
+        let copy = is_rgba_byte_order(rgb_shifts);
+        out.clear();
+        out.reserve(usize::from(self.width) * usize::from(self.height));
+        for row in 0..usize::from(self.height) {
+            let start = row * self.stride_bytes;
+            let (row, _) = self.data[start..start + row_len].as_chunks();
+            if copy {
+                out.extend(row.iter().copied().map(u32::from_ne_bytes));
+            } else {
+                out.extend(
+                    row.iter()
+                        .map(|&[r, g, b, a]| rgb_shifts.from_rgba(r, g, b, a)),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_raw_rgba_byte_order() {
+    let abgr = RgbShifts { a: Some(24), ..RgbShifts::XBGR8888 };
+    let little = cfg!(target_endian = "little");
+    assert_eq!(little, is_rgba_byte_order(abgr));
+    assert_eq!(little, is_rgba_byte_order(RgbShifts::XBGR8888));
+    assert!(!is_rgba_byte_order(RgbShifts::XRGB8888));
+    assert!(!is_rgba_byte_order(RgbShifts::XRGB2101010));
+
+    let data: &[u8] = &[1, 2, 3, 4, 0, 0, 5, 6, 7, 8];
+    let img = RawRgba::new(1, 2, 6, data).unwrap();
+    for shifts in [abgr, RgbShifts::ARGB8888] {
+        let want =
+            [shifts.from_rgba(1u8, 2, 3, 4), shifts.from_rgba(5u8, 6, 7, 8)];
+        assert_eq!(&want, &img.into_x_buffer(shifts).unwrap()[..]);
+    }
+}
+
+impl<'a, S: Clone> RgbImage<'a, S> {
+    /// Applies Floyd–Steinberg dithering when reducing components to eight
+    /// bits.
+    ///
+    /// By default, components with more than eight bits of precision (e.g.
+    /// `u16` subpixels) are simply truncated which may result in visible
+    /// banding on smooth gradients.  The returned object instead diffuses
+    /// quantisation error onto neighbouring pixels.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{RgbImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 4×1 RGB image whose components lie half-way between two
+    /// // 8-bit values.
+    /// let data: &[u16] = &[0x1080; 12][..];
+    /// let img = RgbImage::new(4, 1, data.into()).unwrap().dithered();
+    ///
+    /// // Convert to X Buffer.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// assert_eq!(&[0x101010, 0x111111, 0x101010, 0x111111], &xbuf[..]);
+    /// ```
+    pub fn dithered(self) -> Dithered<'a, S> { Dithered(self) }
+
+    /// Adjusts brightness and contrast of the image when converting it into
+    /// X-compatible image buffer.
+    ///
+    /// Each 8-bit component `v` is replaced by `(v - 128) * contrast + 128 +
+    /// brightness`.  The result is clamped to 0–255 range, thus brightness of
+    /// 255 makes any image white while brightness of -255 makes any image
+    /// black.  Contrast of one and brightness of zero leave the image
+    /// unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{RgbImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 2×1 RGB image and dim it.
+    /// let data: &[u8] = &[0, 128, 255, 100, 100, 100][..];
+    /// let img = RgbImage::new(2, 1, data.into()).unwrap().adjust(-50, 0.5);
+    ///
+    /// // Convert to X Buffer.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// assert_eq!(&[0x0E_4E_8E, 0x40_40_40], &xbuf[..]);
+    /// ```
+    pub fn adjust(self, brightness: i16, contrast: f32) -> Adjusted<'a, S> {
+        Adjusted { img: self, brightness, contrast }
+    }
+
+    /// Inverts colours of the image when converting it into X-compatible image
+    /// buffer.
+    ///
+    /// Each 8-bit component `v` is replaced by `255 - v`.  The image data is
+    /// not copied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{RgbImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 2×1 RGB image with black and orange pixels.
+    /// let data: &[u8] = &[0, 0, 0, 255, 128, 0][..];
+    /// let img = RgbImage::new(2, 1, data.into()).unwrap().inverted();
+    ///
+    /// // Black becomes white.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// assert_eq!(&[0xFF_FF_FF, 0x00_7F_FF], &xbuf[..]);
+    /// ```
+    pub fn inverted(self) -> Inverted<'a, S> { Inverted(self) }
+
+    /// Desaturates the image when converting it into X-compatible image
+    /// buffer.
+    ///
+    /// Luma of each pixel is calculated using Rec. 709 weights, i.e. as
+    /// `0.2126 r + 0.7152 g + 0.0722 b`, on gamma-encoded 8-bit components.
+    /// The image data is not copied so the colour image can still be used
+    /// for other purposes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{RgbImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 2×1 RGB image with red and white pixels.
+    /// let data: &[u8] = &[255, 0, 0, 255, 255, 255][..];
+    /// let img = RgbImage::new(2, 1, data.into()).unwrap().grayscale();
+    ///
+    /// // Convert to X Buffer.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// let want = [shifts.from_luma(54u8), shifts.from_luma(255u8)];
+    /// assert_eq!(&want, &xbuf[..]);
+    /// ```
+    pub fn grayscale(self) -> Grayscale<'a, S> { Grayscale(self) }
+}
+
+/// An RGB image which is dithered when converted into X-compatible image
+/// buffer.
+///
+/// Constructed by [`RgbImage::dithered`].
+#[derive(Clone)]
+pub struct Dithered<'a, S: Clone>(RgbImage<'a, S>);
+
+impl<'a, S: Subpixel> IntoXBuffer<'a> for Dithered<'a, S> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.0.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let img = self.0.0;
+        let width = usize::from(img.dimensions.0);
+        let (chunks, remainder) = img.data.as_chunks::<3>();
+        assert_eq!(0, remainder.len());
+
+        // Accumulated errors (multiplied by 16) for the current and next row.
+        // Rows are padded by one pixel on each side so the error can be
+        // diffused without checking for edges.
+        let mut errors = vec![[0i32; 3]; width + 2];
+        let mut next = vec![[0i32; 3]; width + 2];
+        out.clear();
+        out.reserve(chunks.len());
+        for row in chunks.chunks(width.max(1)) {
+            for (x, pixel) in row.iter().enumerate() {
+                let mut rgb = [0u8; 3];
+                for ch in 0..3 {
+                    let value =
+                        i32::from(pixel[ch].to_u16()) + errors[x + 1][ch] / 16;
+                    let value = value.clamp(0, 0xFFFF);
+                    rgb[ch] = ((value + 128) / 257) as u8;
+                    let err = value - i32::from(rgb[ch]) * 257;
+                    errors[x + 2][ch] += err * 7;
+                    next[x][ch] += err * 3;
+                    next[x + 1][ch] += err * 5;
+                    next[x + 2][ch] += err;
+                }
+                out.push(rgb_shifts.from_rgb(rgb[0], rgb[1], rgb[2]));
+            }
+            core::mem::swap(&mut errors, &mut next);
+            next.fill([0; 3]);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_dithered_gradient() {
+    // 256×4 gradient spanning four 8-bit values.
+    let data = (0..4)
+        .flat_map(|_| (0..256u16).flat_map(|x| [0x4000 + x * 4; 3]))
+        .collect::<Vec<_>>();
+    let img = RgbImage::new(256, 4, data.into()).unwrap();
+    let count_distinct = |buf: XBuffer| {
+        let mut values = buf.0;
+        values.sort();
+        values.dedup();
+        values.len()
+    };
+
+    let plain = img.clone().into_x_buffer(RgbShifts::XRGB8888).unwrap();
+    let dithered = img.dithered().into_x_buffer(RgbShifts::XRGB8888).unwrap();
+
+    // Dithering mixes neighbouring values so average over a column block
+    // should track the original gradient.
+    let luma = |buf: &XBuffer, x: usize| {
+        (0..4).map(|y| buf[y * 256 + x] & 0xFF).sum::<u32>() as f32 / 4.0
+    };
+    let block = |buf: &XBuffer, start: usize| {
+        (start..start + 16).map(|x| luma(buf, x)).sum::<f32>() / 16.0
+    };
+    for start in (0..256).step_by(16) {
+        let expected = (16384.0 + (start as f32 + 7.5) * 4.0) / 257.0;
+        assert!((block(&dithered, start) - expected).abs() < 0.25);
+    }
+
+    assert_eq!(4, count_distinct(plain));
+    assert!(count_distinct(dithered) > 4);
+}
+
+/// An RGB image whose brightness and contrast are adjusted when converted into
+/// X-compatible image buffer.
+///
+/// Constructed by [`RgbImage::adjust`].
+#[derive(Clone)]
+pub struct Adjusted<'a, S: Clone> {
+    img: RgbImage<'a, S>,
+    brightness: i16,
+    contrast: f32,
+}
+
+impl<'a, S: Subpixel> IntoXBuffer<'a> for Adjusted<'a, S> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.img.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let (brightness, contrast) =
+            (f32::from(self.brightness), self.contrast);
+        let lut: [u8; 256] = core::array::from_fn(|v| {
+            let v = (v as f32 - 128.0) * contrast + 128.0 + brightness;
+            round(f64::from(v)).clamp(0.0, 255.0) as u8
+        });
+        let (chunks, remainder) = self.img.0.data.as_chunks::<3>();
+        assert_eq!(0, remainder.len());
+        out.clear();
+        out.extend(chunks.iter().map(|[r, g, b]| {
+            let [r, g, b] = [r, g, b].map(|v| lut[usize::from(v.to_u8())]);
+            rgb_shifts.from_rgb(r, g, b)
+        }));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_adjust() {
+    let data: &[u8] = &[0, 0, 0, 12, 128, 255];
+    let img = RgbImage::new(2, 1, data.into()).unwrap();
+    let convert = |brightness, contrast| {
+        let img = img.clone().adjust(brightness, contrast);
+        img.into_x_buffer(RgbShifts::XRGB8888).unwrap().0
+    };
+
+    assert_eq!(vec![0x00_00_00, 0x0C_80_FF], convert(0, 1.0));
+    assert_eq!(vec![0xFF_FF_FF; 2], convert(255, 1.0));
+    assert_eq!(vec![0x00_00_00; 2], convert(-255, 1.0));
+    assert_eq!(vec![0x80_80_80; 2], convert(0, 0.0));
+    assert_eq!(vec![0x00_00_00, 0x00_80_FF], convert(0, 2.0));
+}
+
+/// An RGB image which is desaturated when converted into X-compatible image
+/// buffer.
+///
+/// Constructed by [`RgbImage::grayscale`].
+#[derive(Clone)]
+pub struct Grayscale<'a, S: Clone>(RgbImage<'a, S>);
+
+impl<'a, S: Subpixel> IntoXBuffer<'a> for Grayscale<'a, S> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.0.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let (chunks, remainder) = self.0.0.data.as_chunks::<3>();
+        assert_eq!(0, remainder.len());
+        out.clear();
+        out.extend(chunks.iter().map(|[r, g, b]| {
+            rgb_shifts.from_luma(rec709_luma(r.to_u8(), g.to_u8(), b.to_u8()))
+        }));
+        Ok(())
+    }
+}
+
+/// Calculates luma of a colour using Rec. 709 weights.
+///
+/// The weights are scaled to 16-bit fixed-point numbers which sum to 65536 so
+/// that white maps to 255.
+fn rec709_luma(r: u8, g: u8, b: u8) -> u8 {
+    let luma =
+        u32::from(r) * 13933 + u32::from(g) * 46871 + u32::from(b) * 4732;
+    ((luma + 0x8000) >> 16) as u8
+}
+
+#[test]
+fn test_rec709_luma() {
+    assert_eq!(54, rec709_luma(255, 0, 0));
+    assert_eq!(182, rec709_luma(0, 255, 0));
+    assert_eq!(18, rec709_luma(0, 0, 255));
+    assert_eq!(0, rec709_luma(0, 0, 0));
+    assert_eq!(255, rec709_luma(255, 255, 255));
+    assert_eq!(128, rec709_luma(128, 128, 128));
+}
+
+/// An RGB image whose colours are inverted when converted into X-compatible
+/// image buffer.
+///
+/// Constructed by [`RgbImage::inverted`].
+#[derive(Clone)]
+pub struct Inverted<'a, S: Clone>(RgbImage<'a, S>);
+
+impl<'a, S: Subpixel> IntoXBuffer<'a> for Inverted<'a, S> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.0.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let (chunks, remainder) = self.0.0.data.as_chunks::<3>();
+        assert_eq!(0, remainder.len());
+        out.clear();
+        out.extend(chunks.iter().map(|[r, g, b]| {
+            let [r, g, b] = [r, g, b].map(|v| 255 - v.to_u8());
+            rgb_shifts.from_rgb(r, g, b)
+        }));
+        Ok(())
+    }
+}
+
+impl<'a, S: Clone> RgbaImage<'a, S> {
+    /// Composites the image over a solid background colour.
+    ///
+    /// By default, alpha channel of the image is ignored (or passed as is if
+    /// display server’s pixel format includes alpha channel).  The returned
+    /// object instead blends each pixel with the `background` colour according
+    /// to its opacity producing a fully opaque image.
+    ///
+    /// The compositing is done in sRGB space on components reduced to 8-bit
+    /// values.  This is fast but not physically accurate and, for
+    /// higher-precision subpixels, results in rounding errors of up to one
+    /// unit in the final 8-bit colour.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use setroot::img::{RgbaImage, RgbShifts};
+    /// use setroot::img::IntoXBuffer;
+    ///
+    /// // Construct 3×1 RGBA image with opaque, semi-transparent and fully
+    /// // transparent pixels.
+    /// let data: &[u8] = &[200, 0, 0, 255, 200, 0, 0, 128, 200, 0, 0, 0][..];
+    /// let img = RgbaImage::new(3, 1, data.into()).unwrap();
+    /// let img = img.with_background((0, 0, 100));
+    ///
+    /// // Convert to X Buffer.
+    /// let shifts = RgbShifts::XRGB8888;
+    /// let xbuf = img.into_x_buffer(shifts).unwrap();
+    /// assert_eq!(&[0xC8_00_00, 0x64_00_32, 0x00_00_64], &xbuf[..]);
+    /// ```
+    pub fn with_background(
+        self,
+        background: (u8, u8, u8),
+    ) -> Composited<'a, S> {
+        Composited { img: self, background }
+    }
+}
+
+/// An RGBA image composited over a solid background colour.
+///
+/// Constructed by [`RgbaImage::with_background`].
+#[derive(Clone)]
+pub struct Composited<'a, S: Clone> {
+    img: RgbaImage<'a, S>,
+    background: (u8, u8, u8),
+}
+
+impl<'a, S: Subpixel> IntoXBuffer<'a> for Composited<'a, S> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.img.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let (chunks, remainder) = self.img.0.data.as_chunks();
+        assert_eq!(0, remainder.len());
+        let (bg_r, bg_g, bg_b) = self.background;
+        out.clear();
+        out.extend(chunks.iter().map(|&[r, g, b, alpha]| {
+            let alpha = alpha.to_u8();
+            rgb_shifts.from_rgb(
+                blend(r.to_u8(), bg_r, alpha),
+                blend(g.to_u8(), bg_g, alpha),
+                blend(b.to_u8(), bg_b, alpha),
+            )
+        }));
+        Ok(())
+    }
+}
+
+/// An image positioned on a surface with given opacity.
+///
+/// Used by [`crate::RootPixmap::composite`] which draws multiple layers in
+/// a single operation.  Layer’s opacity is multiplied by the alpha channel
+/// of the image (if any) and is clamped to 0–1 range.
+#[derive(Clone, Debug)]
+pub struct Layer<I> {
+    /// Horizontal position of the image’s top-left corner.
+    pub x: i16,
+    /// Vertical position of the image’s top-left corner.
+    pub y: i16,
+    /// Opacity of the layer where 0 is fully transparent and 1 is fully
+    /// opaque.
+    pub opacity: f32,
+    /// The image.
+    pub img: I,
+}
+
+/// An image mirrored horizontally, i.e. with order of pixels in each row
+/// reversed.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{FlipH, RgbImage, RgbShifts};
+/// use setroot::img::IntoXBuffer;
+///
+/// let data: &[u8] = &[1, 2, 3, 4, 5, 6][..];
+/// let img = FlipH(RgbImage::new(2, 1, data.into()).unwrap());
+///
+/// let xbuf = img.into_x_buffer(RgbShifts::XRGB8888).unwrap();
+/// assert_eq!(&[0x04_05_06, 0x01_02_03], &xbuf[..]);
+/// ```
+#[derive(Clone)]
+pub struct FlipH<I>(pub I);
+
+impl<'a, I: IntoXBuffer<'a>> IntoXBuffer<'a> for FlipH<I> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.0.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let width = usize::from(self.0.dimensions()?.0);
+        self.0.into_x_buffer_in(rgb_shifts, out)?;
+        if width != 0 {
+            out.chunks_exact_mut(width).for_each(<[u32]>::reverse);
+        }
+        Ok(())
+    }
+}
+
+/// An image mirrored vertically, i.e. with order of rows reversed.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{FlipV, RgbImage, RgbShifts};
+/// use setroot::img::IntoXBuffer;
+///
+/// let data: &[u8] = &[1, 2, 3, 4, 5, 6][..];
+/// let img = FlipV(RgbImage::new(1, 2, data.into()).unwrap());
+///
+/// let xbuf = img.into_x_buffer(RgbShifts::XRGB8888).unwrap();
+/// assert_eq!(&[0x04_05_06, 0x01_02_03], &xbuf[..]);
+/// ```
+#[derive(Clone)]
+pub struct FlipV<I>(pub I);
+
+impl<'a, I: IntoXBuffer<'a>> IntoXBuffer<'a> for FlipV<I> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.0.dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        let width = usize::from(self.0.dimensions()?.0);
+        self.0.into_x_buffer_in(rgb_shifts, out)?;
+        if width != 0 {
+            // Swap rows from the top half with rows from the bottom half.
+            let mut rows = out.chunks_exact_mut(width);
+            while let (Some(top), Some(bottom)) =
+                (rows.next(), rows.next_back())
+            {
+                top.swap_with_slice(bottom);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Blends two 8-bit component values.
+///
+/// Returns `src * alpha + dst * (1 - alpha)` where alpha is expressed in 0–255
+/// range.
+pub(crate) fn blend(src: u8, dst: u8, alpha: u8) -> u8 {
+    let alpha = u32::from(alpha);
+    let val = u32::from(src) * alpha + u32::from(dst) * (255 - alpha);
+    ((val + 127) / 255) as u8
+}
+
+/// Blends two images in format accepted by the X display server.
+///
+/// Returns `a * (1 - t) + b * t` computed for each byte of the pixels which
+/// works for any pixel format with 8-bit channels.  With 10-bit channels, the
+/// colour components are blended instead.  `t` is clamped to 0–1 range.  The
+/// slices must be the same length.
+pub(crate) fn crossfade(
+    a: &[u32],
+    b: &[u32],
+    t: f32,
+    rgb_shifts: RgbShifts,
+) -> Vec<u32> {
+    let t = t.clamp(0.0, 1.0);
+    let pixels = a.iter().zip(b.iter());
+    if rgb_shifts.bits != 8 {
+        let mix = |a: u32, b: u32| {
+            rgb_shifts.channels().into_iter().fold(
+                0,
+                |colour, (shift, bits)| {
+                    let max = max_value(bits);
+                    let (a, b) = ((a >> shift) & max, (b >> shift) & max);
+                    let value = a as f32 + (b as f32 - a as f32) * t;
+                    colour | ((round(f64::from(value)) as u32) << shift)
+                },
+            )
+        };
+        return pixels.map(|(&a, &b)| mix(a, b)).collect();
+    }
+    let alpha = round(f64::from(t * 255.0)) as u8;
+    pixels
+        .map(|(a, b)| {
+            let (a, b) = (a.to_ne_bytes(), b.to_ne_bytes());
+            u32::from_ne_bytes(core::array::from_fn(|i| {
+                blend(b[i], a[i], alpha)
+            }))
+        })
+        .collect()
+}
+
+#[test]
+fn test_crossfade() {
+    let (a, b) =
+        ([0x00_00_00_00, 0x00_FF_80_10], [0x00_FF_FF_FF, 0x00_00_80_30]);
+    let shifts = RgbShifts::XRGB8888;
+    assert_eq!(a.to_vec(), crossfade(&a, &b, 0.0, shifts));
+    assert_eq!(a.to_vec(), crossfade(&a, &b, -1.0, shifts));
+    assert_eq!(b.to_vec(), crossfade(&a, &b, 1.0, shifts));
+    assert_eq!(b.to_vec(), crossfade(&a, &b, 2.0, shifts));
+    let want = vec![0x00_80_80_80, 0x00_7F_80_20];
+    assert_eq!(want, crossfade(&a, &b, 0.5, shifts));
+
+    let shifts = RgbShifts::XRGB2101010;
+    let (a, b) = ([0x3FF_00000], [0x000_FFC00]);
+    assert_eq!(vec![0x200_80000], crossfade(&a, &b, 0.5, shifts));
+}
+
+#[test]
+fn test_buffer_size_mismatch() {
+    // 2×2 image with 4 pixels = 12 bytes
+    let data: [u8; 13] = [0; 13];
+    let res = RgbImage::new(2, 2, (&data[..11]).into());
+    assert!(matches!(res, Err(Error::BadBufferSize(11, 2, 2))));
+    let res = RgbImage::new(2, 2, (&data[..13]).into());
+    assert!(matches!(res, Err(Error::BadBufferSize(13, 2, 2))));
+    RgbImage::new(2, 2, (&data[..12]).into()).unwrap();
+}
+
+/// An in-memory canvas images can be drawn onto without an X display server.
+///
+/// The canvas offers the same drawing methods as [`crate::RootPixmap`] and
+/// uses the same placement and scaling logic.  This makes it possible to
+/// compute exactly what would be drawn on the root pixmap, e.g. to save
+/// a preview of a wallpaper composition, without connecting to a display.
+/// Pixels are stored in format described by [`RgbShifts`] passed when the
+/// canvas is created.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::{MemoryCanvas, RgbImage, RgbShifts, ScaleMode, Filter};
+/// let canvas = MemoryCanvas::new(4, 2, RgbShifts::XRGB8888);
+/// canvas.fill_solid(0x00_00_00_FF).unwrap();
+///
+/// let data: &[u8] = &[255, 0, 0];
+/// let img = RgbImage::new(1, 1, data.into()).unwrap();
+/// canvas.put_image_scaled(ScaleMode::Fit, Filter::Nearest, img).unwrap();
+///
+/// let (red, blue) = (0x00_FF_00_00, 0x00_00_00_FF);
+/// let want = [blue, red, red, blue, blue, red, red, blue];
+/// assert_eq!(&want, &*canvas.pixels());
+///
+/// let rgb = canvas.to_rgb_image();
+/// # let _ = rgb;
+/// ```
+#[cfg(feature = "std")]
+pub struct MemoryCanvas {
+    dimensions: (u16, u16),
+    rgb_shifts: RgbShifts,
+    pixels: core::cell::RefCell<Vec<u32>>,
+}
+
+#[cfg(feature = "std")]
+impl MemoryCanvas {
+    /// Creates a new canvas of given dimensions with all pixels set to zero.
+    pub fn new(width: u16, height: u16, rgb_shifts: RgbShifts) -> Self {
+        let pixels = vec![0; usize::from(width) * usize::from(height)];
+        Self { dimensions: (width, height), rgb_shifts, pixels: pixels.into() }
+    }
+
+    /// Returns dimensions of the canvas.
+    pub fn dimensions(&self) -> (u16, u16) { self.dimensions }
+
+    /// Returns RGB shifts which define pixel format used by the canvas.
+    pub fn rgb_shifts(&self) -> RgbShifts { self.rgb_shifts }
+
+    /// Returns pixels of the canvas in row-major order.
+    pub fn pixels(&self) -> core::cell::Ref<'_, [u32]> {
+        core::cell::Ref::map(self.pixels.borrow(), Vec::as_slice)
+    }
+
+    /// Consumes the canvas and returns its pixels in row-major order.
+    pub fn into_pixels(self) -> Vec<u32> { self.pixels.into_inner() }
+
+    /// Converts the canvas into an RGB image.
+    ///
+    /// The pixels are converted using [`RgbShifts::to_rgb`].  The image can be
+    /// drawn on a [`crate::RootPixmap`] or, with `image` feature, converted
+    /// into an `image::RgbImage` and saved to a file.
+    pub fn to_rgb_image(&self) -> RgbImage<'static, u8> {
+        let (width, height) = self.dimensions;
+        let data = self
+            .pixels
+            .borrow()
+            .iter()
+            .flat_map(|&px| {
+                let (r, g, b) = self.rgb_shifts.to_rgb(px);
+                [r, g, b]
+            })
+            .collect::<Vec<u8>>();
+        RgbImage::new(width.into(), height.into(), data.into()).unwrap()
+    }
+
+    /// Puts an image at given location on the canvas.  See
+    /// [`crate::RootPixmap::put_image`].
+    pub fn put_image<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl IntoXBuffer<'b>,
+    ) -> crate::Result {
+        Surface::put_image(self, dst_x, dst_y, img)
+    }
+
+    /// Puts an image on the canvas scaling it according to given mode.  See
+    /// [`crate::RootPixmap::put_image_scaled`].
+    pub fn put_image_scaled<'b>(
+        &self,
+        mode: ScaleMode,
+        filter: Filter,
+        img: impl IntoXBuffer<'b>,
+    ) -> crate::Result<crate::Rect> {
+        Surface::put_image_scaled(self, mode, filter, img)
+    }
+
+    /// Puts an image on the canvas scaling it according to given mode and
+    /// anchoring it according to given gravity.  See
+    /// [`crate::RootPixmap::put_image_scaled_with_gravity`].
+    pub fn put_image_scaled_with_gravity<'b>(
+        &self,
+        mode: ScaleMode,
+        gravity: Gravity,
+        filter: Filter,
+        img: impl IntoXBuffer<'b>,
+    ) -> crate::Result<crate::Rect> {
+        Surface::put_image_scaled_with_gravity(self, mode, gravity, filter, img)
+    }
+
+    /// Composites multiple images onto the canvas.  See
+    /// [`crate::RootPixmap::composite`].
+    pub fn composite<'b, I: IntoXBuffer<'b> + Clone>(
+        &self,
+        layers: &[Layer<I>],
+    ) -> crate::Result {
+        Surface::composite(self, layers)
+    }
+
+    /// Repeats an image across the entire canvas.  See
+    /// [`crate::RootPixmap::put_image_tiled`].
+    pub fn put_image_tiled<'b>(
+        &self,
+        offset_x: i16,
+        offset_y: i16,
+        img: impl IntoXBuffer<'b>,
+    ) -> crate::Result {
+        Surface::put_image_tiled(self, offset_x, offset_y, img)
+    }
+
+    /// Repeats an image across the entire canvas mirroring every other copy.
+    /// See [`crate::RootPixmap::put_image_tiled_mirrored`].
+    pub fn put_image_tiled_mirrored<'b>(
+        &self,
+        offset_x: i16,
+        offset_y: i16,
+        img: impl IntoXBuffer<'b>,
+    ) -> crate::Result {
+        Surface::put_image_tiled_mirrored(self, offset_x, offset_y, img)
+    }
+
+    /// Puts an image in the canvas’ pixel format at given location.  See
+    /// [`crate::RootPixmap::put_raw`].
+    pub fn put_raw(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u32],
+    ) -> crate::Result {
+        Surface::put_raw(self, dst_x, dst_y, width, height, data)
+    }
+
+    /// Puts an image given as bytes in the canvas’ pixel format at given
+    /// location.  See [`crate::RootPixmap::put_raw_bytes`].
+    pub fn put_raw_bytes(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> crate::Result {
+        Surface::put_raw_bytes(self, dst_x, dst_y, width, height, data)
+    }
+
+    /// Fills the entire canvas with a solid colour.  See
+    /// [`crate::RootPixmap::fill_solid`].
+    pub fn fill_solid(&self, colour: u32) -> crate::Result {
+        Surface::fill_solid(self, colour)
+    }
+
+    /// Fills given rectangle of the canvas with a solid colour.  See
+    /// [`crate::RootPixmap::fill_rect`].
+    pub fn fill_rect(
+        &self,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        colour: u32,
+    ) -> crate::Result {
+        Surface::fill_rect(self, x, y, width, height, colour)
+    }
+
+    /// Fills the entire canvas with a checkerboard.  See
+    /// [`crate::RootPixmap::fill_checkerboard`].
+    pub fn fill_checkerboard(
+        &self,
+        a: u32,
+        b: u32,
+        cell: u16,
+    ) -> crate::Result {
+        Surface::fill_checkerboard(self, a, b, cell)
+    }
+
+    /// Fills the entire canvas with a linear gradient.  See
+    /// [`crate::RootPixmap::fill_linear_gradient`].
+    pub fn fill_linear_gradient(
+        &self,
+        start: (u8, u8, u8),
+        end: (u8, u8, u8),
+        direction: GradientDirection,
+        dither: Dither,
+    ) -> crate::Result {
+        Surface::fill_linear_gradient(self, start, end, direction, dither)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Surface for MemoryCanvas {
+    fn dimensions(&self) -> (u16, u16) { self.dimensions }
+
+    fn rgb_shifts(&self) -> RgbShifts { self.rgb_shifts }
+
+    fn put_raw_impl(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> crate::Result {
+        let (canvas_w, canvas_h) = self.dimensions;
+        let mut pixels = self.pixels.borrow_mut();
+        let (chunks, _) = data.as_chunks();
+        let rows = chunks.chunks(usize::from(width).max(1));
+        for (row, line) in rows.take(usize::from(height)).enumerate() {
+            let y = i32::from(dst_y) + row as i32;
+            if !(0..i32::from(canvas_h)).contains(&y) {
+                continue;
+            }
+            for (col, &px) in line.iter().enumerate() {
+                let x = i32::from(dst_x) + col as i32;
+                if (0..i32::from(canvas_w)).contains(&x) {
+                    let idx = y as usize * usize::from(canvas_w) + x as usize;
+                    pixels[idx] = u32::from_ne_bytes(px);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_rects_impl(
+        &self,
+        rects: &[xcb::x::Rectangle],
+        colour: u32,
+    ) -> crate::Result {
+        let stride = usize::from(self.dimensions.0);
+        let mut pixels = self.pixels.borrow_mut();
+        for rect in rects {
+            for y in 0..usize::from(rect.height) {
+                let start = (rect.y as usize + y) * stride + rect.x as usize;
+                pixels[start..start + usize::from(rect.width)].fill(colour);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_raw_impl(&self, rect: xcb::x::Rectangle) -> crate::Result<Vec<u32>> {
+        let stride = usize::from(self.dimensions.0);
+        let pixels = self.pixels.borrow();
+        let mut data = Vec::with_capacity(
+            usize::from(rect.width) * usize::from(rect.height),
+        );
+        for y in 0..usize::from(rect.height) {
+            let start = (rect.y as usize + y) * stride + rect.x as usize;
+            data.extend_from_slice(&pixels[start..][..usize::from(rect.width)]);
+        }
+        Ok(data)
+    }
+}
+
+
+#[cfg(feature = "image")]
+impl IntoXBuffer<'static> for image::DynamicImage {
     type Buffer = Vec<u8>;
 
     fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
@@ -396,7 +3498,7 @@ impl IntoXBuffer<'static> for image::DynamicImage {
     /// assert_eq!(Ok((2, 2)), img.dimensions());
     ///
     /// // Convert to X Buffer.
-    /// let shifts = RgbShifts { r: 16, g: 8, b: 0 };
+    /// let shifts = RgbShifts::XRGB8888;
     /// let xbuf = img.into_x_buffer(shifts).unwrap();
     /// let xbuf: &[u8] = xbuf.as_ref();
     /// let pixels = xbuf.as_chunks::<4>()
@@ -437,6 +3539,11 @@ impl IntoXBuffer<'static> for &image::DynamicImage {
         self,
         rgb_shifts: RgbShifts,
     ) -> crate::Result<Self::Buffer> {
+        if self.color_space() == image::metadata::Cicp::SRGB &&
+            let Some(data) = pack_dynamic_image(self, rgb_shifts)
+        {
+            return Ok(data);
+        }
         // https://github.com/image-rs/image/discussions/2650#discussioncomment-15015296
         let (width, height) = image::GenericImageView::dimensions(self);
         let img = image::RgbaImage::new(width, height);
@@ -447,15 +3554,405 @@ impl IntoXBuffer<'static> for &image::DynamicImage {
     }
 }
 
+/// Converts greyscale, 8-bit RGB and RGBA and 16-bit images in sRGB colour
+/// space directly into format supported by the X display server.
+///
+/// This avoids expanding greyscale and RGB images into 8-bit RGBA first (RGB
+/// pixels are packed the same way [`RgbImage`] packs them) and, with
+/// visuals with 10-bit channels, preserves precision of 16-bit images.  8-bit
+/// RGBA images are copied without any conversion if the channel order matches
+/// the visual.  Returns `None` for other variants which need to go through
+/// the generic conversion.
+#[cfg(feature = "image")]
+fn pack_dynamic_image(
+    img: &image::DynamicImage,
+    rgb_shifts: RgbShifts,
+) -> Option<Vec<u8>> {
+    use image::DynamicImage as Img;
+    let shifts = rgb_shifts;
+    Some(match img {
+        Img::ImageRgba8(img) => fix_channel_order(img.as_raw().clone(), shifts),
+        Img::ImageLuma8(img) => pack_bytes(img, |&[y]| shifts.from_luma(y)),
+        Img::ImageLuma16(img) => pack_bytes(img, |&[y]| shifts.from_luma(y)),
+        Img::ImageLumaA8(img) => {
+            pack_bytes(img, |&[y, a]| shifts.from_rgba(y, y, y, a))
+        }
+        Img::ImageLumaA16(img) => {
+            pack_bytes(img, |&[y, a]| shifts.from_rgba(y, y, y, a))
+        }
+        Img::ImageRgb8(img) => {
+            pack_bytes(img, |&[r, g, b]| shifts.from_rgb(r, g, b))
+        }
+        Img::ImageRgb16(img) => {
+            pack_bytes(img, |&[r, g, b]| shifts.from_rgb(r, g, b))
+        }
+        Img::ImageRgba16(img) => {
+            pack_bytes(img, |&[r, g, b, a]| shifts.from_rgba(r, g, b, a))
+        }
+        _ => return None,
+    })
+}
+
+/// Packs each `N`-channel pixel of an image with given function and returns
+/// the result as bytes.
+#[cfg(feature = "image")]
+fn pack_bytes<S: Subpixel, const N: usize>(
+    data: &[S],
+    pack: impl Fn(&[S; N]) -> u32 + Sync,
+) -> Vec<u8> {
+    let (pixels, remainder) = data.as_chunks::<N>();
+    assert_eq!(0, remainder.len());
+    let mut out = vec![0; pixels.len() * 4];
+    let (chunks, _) = out.as_chunks_mut::<4>();
+    let store = |(dst, src): (&mut [u8; 4], &[S; N])| {
+        *dst = pack(src).to_ne_bytes();
+    };
+    #[cfg(not(feature = "rayon"))]
+    chunks.iter_mut().zip(pixels).for_each(store);
+    #[cfg(feature = "rayon")]
+    rayon::iter::ParallelIterator::for_each(
+        rayon::iter::IndexedParallelIterator::zip(
+            rayon::iter::IntoParallelIterator::into_par_iter(chunks),
+            pixels,
+        ),
+        store,
+    );
+    out
+}
+
+#[test]
+#[cfg(feature = "image")]
+fn test_pack_dynamic_image() {
+    let convert = |img: image::DynamicImage, shifts| {
+        let data = img.into_x_buffer(shifts).unwrap();
+        let chunks = data.as_chunks().0.iter();
+        chunks.map(|px| u32::from_ne_bytes(*px)).collect::<Vec<_>>()
+    };
+    let shifts = RgbShifts::ARGB8888;
+
+    let luma = image::GrayImage::from_raw(2, 1, vec![7, 42]).unwrap();
+    let got = convert(luma.into(), shifts);
+    assert_eq!(&[0xFF070707, 0xFF2A2A2A], &got[..]);
+
+    let luma_a = image::GrayAlphaImage::from_raw(1, 1, vec![7, 9]).unwrap();
+    let got = convert(luma_a.into(), shifts);
+    assert_eq!(&[0x09070707], &got[..]);
+
+    let rgb = image::RgbImage::from_raw(2, 1, vec![1, 2, 3, 4, 5, 6]).unwrap();
+    let rgb = image::DynamicImage::from(rgb);
+    let want = RgbImage::new(2, 1, rgb.as_bytes().into()).unwrap();
+    let want = want.into_x_buffer(shifts).unwrap();
+    assert_eq!(&want[..], &convert(rgb, shifts)[..]);
+    assert_eq!(&[0xFF010203, 0xFF040506], &want[..]);
+
+    let rgba = image::RgbaImage::from_raw(1, 1, vec![1, 2, 3, 4]).unwrap();
+    let rgba = image::DynamicImage::from(rgba);
+    let data = (&rgba).into_x_buffer(shifts).unwrap();
+    assert_eq!(0x04010203u32.to_ne_bytes(), &data[..]);
+
+    let data = vec![0x8040, 0xFFFF, 0, 0x4000];
+    let rgba = image::ImageBuffer::<image::Rgba<u16>, _>::from_raw(1, 1, data);
+    let got = convert(rgba.unwrap().into(), shifts);
+    assert_eq!(&[0x4080FF00], &got[..]);
+
+    // 16-bit precision is preserved with 10-bit channels.
+    let data = vec![0x8040, 0xFFFF, 0];
+    let rgb = image::ImageBuffer::<image::Rgb<u16>, _>::from_raw(1, 1, data);
+    let rgb = rgb.unwrap();
+    let got = convert(rgb.clone().into(), RgbShifts::XRGB2101010);
+    assert_eq!(&[(0x201 << 20) | (0x3FF << 10)], &got[..]);
+    let got = convert(
+        image::DynamicImage::from(rgb).into_rgb8().into(),
+        RgbShifts::XRGB2101010,
+    );
+    assert_eq!(&[(0x202 << 20) | (0x3FF << 10)], &got[..]);
+}
+
+/// Implements [`IntoXBuffer`] for a reference to an `image` crate buffer.
+///
+/// Images in sRGB colour space (which is the default) are converted directly
+/// from the borrowed container using the crate’s own image types.  Anything
+/// else goes through the [`image::DynamicImage`] path so that the colour
+/// space is converted.
+#[cfg(feature = "image")]
+macro_rules! impl_image_buffer {
+    ($($Pixel:ident => $Image:ident),* $(,)?) => {$(
+        impl<'a> IntoXBuffer<'a>
+            for &'a image::ImageBuffer<image::$Pixel<u8>, Vec<u8>>
+        {
+            type Buffer = XBuffer;
+
+            fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+                new_dimensions(image::ImageBuffer::dimensions(*self))
+            }
+
+            fn into_x_buffer(
+                self,
+                rgb_shifts: RgbShifts,
+            ) -> crate::Result<Self::Buffer> {
+                XBuffer::from_image(self, rgb_shifts)
+            }
+
+            fn into_x_buffer_in(
+                self,
+                rgb_shifts: RgbShifts,
+                out: &mut Vec<u32>,
+            ) -> crate::Result {
+                if self.color_space() == image::metadata::Cicp::SRGB {
+                    let (width, height) = self.dimensions();
+                    let data = Cow::Borrowed(self.as_raw().as_slice());
+                    return $Image::new(width, height, data)?
+                        .into_x_buffer_in(rgb_shifts, out);
+                }
+                let img = image::DynamicImage::from(self.clone());
+                let data = (&img).into_x_buffer(rgb_shifts)?;
+                out.clear();
+                out.extend(data.as_chunks().0.iter().map(|px| {
+                    u32::from_ne_bytes(*px)
+                }));
+                Ok(())
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "image")]
+impl_image_buffer! {
+    Rgb => RgbImage,
+    Rgba => RgbaImage,
+    Luma => LumaImage,
+    LumaA => LumaAImage,
+}
+
+#[test]
+#[cfg(feature = "image")]
+fn test_image_buffer_ref() {
+    let shifts = RgbShifts::XRGB8888;
+    // Padding byte isn’t consistent between the paths; ignore it.
+    let mask = |px: &[u32]| px.iter().map(|px| px & 0xFF_FF_FF).collect();
+    let convert = |img: image::DynamicImage| -> Vec<u32> {
+        let data = img.into_x_buffer(shifts).unwrap();
+        let chunks = data.as_chunks().0.iter();
+        chunks.map(|px| u32::from_ne_bytes(*px) & 0xFF_FF_FF).collect()
+    };
+
+    let rgb = image::RgbImage::from_raw(2, 1, vec![1, 2, 3, 4, 5, 6]).unwrap();
+    assert_eq!(Ok((2, 1)), IntoXBuffer::dimensions(&&rgb));
+    let want = convert(rgb.clone().into());
+    let got: Vec<u32> = mask(&(&rgb).into_x_buffer(shifts).unwrap());
+    assert_eq!(want, got);
+    assert_eq!(&[0x010203, 0x040506], &want[..]);
+
+    let rgba = image::RgbaImage::from_raw(1, 1, vec![1, 2, 3, 4]).unwrap();
+    let want = convert(rgba.clone().into());
+    let got: Vec<u32> = mask(&(&rgba).into_x_buffer(shifts).unwrap());
+    assert_eq!(want, got);
+
+    let luma = image::GrayImage::from_raw(2, 1, vec![7, 42]).unwrap();
+    let want = convert(luma.clone().into());
+    let got: Vec<u32> = mask(&(&luma).into_x_buffer(shifts).unwrap());
+    assert_eq!(want, got);
+
+    let luma_a = image::GrayAlphaImage::from_raw(1, 1, vec![7, 9]).unwrap();
+    let got: Vec<u32> = mask(&(&luma_a).into_x_buffer(shifts).unwrap());
+    assert_eq!(&[0x070707], &got[..]);
+
+    // Non-sRGB images go through the colour space conversion.
+    let mut rgb = rgb;
+    rgb.set_color_space(image::metadata::Cicp::DISPLAY_P3).unwrap();
+    let want = convert(rgb.clone().into());
+    let got: Vec<u32> = mask(&(&rgb).into_x_buffer(shifts).unwrap());
+    assert_eq!(want, got);
+}
+
 #[cfg(feature = "image")]
 fn fix_channel_order(mut data: Vec<u8>, rgb_shifts: RgbShifts) -> Vec<u8> {
-    if rgb_shifts.from_rgb(1u8, 2u8, 3u8).to_ne_bytes() != [1u8, 2, 3, 0] {
+    if !is_rgba_byte_order(rgb_shifts) {
         let (chunks, remainder) = data.as_chunks_mut();
         assert_eq!(0, remainder.len());
         for chunk in chunks {
-            let [r, g, b, _] = *chunk;
-            *chunk = rgb_shifts.from_rgb(r, g, b).to_ne_bytes();
+            let [r, g, b, a] = *chunk;
+            *chunk = rgb_shifts.from_rgba(r, g, b, a).to_ne_bytes();
         }
     }
     data
 }
+
+
+/// Converts a `tiny-skia` pixmap into X-compatible image buffer.
+///
+/// `tiny-skia` stores pixels as premultiplied RGBA so the colours are
+/// unpremultiplied before being packed.
+///
+/// # Example
+///
+/// ```
+/// # use setroot::img::RgbShifts;
+/// use setroot::img::IntoXBuffer;
+///
+/// let mut pixmap = tiny_skia::Pixmap::new(2, 1).unwrap();
+/// pixmap.fill(tiny_skia::Color::from_rgba8(255, 0, 0, 255));
+///
+/// assert_eq!(Ok((2, 1)), pixmap.as_ref().dimensions());
+///
+/// let xbuf = pixmap.into_x_buffer(RgbShifts::XRGB8888).unwrap();
+/// assert_eq!(&[0xFF_00_00, 0xFF_00_00], &xbuf[..]);
+/// ```
+#[cfg(feature = "tiny-skia")]
+impl<'a> IntoXBuffer<'a> for tiny_skia::PixmapRef<'a> {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        new_dimensions((self.width(), self.height()))
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        XBuffer::from_image(self, rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        IntoXBuffer::dimensions(&self)?;
+        out.clear();
+        out.extend(self.pixels().iter().map(|px| {
+            let px = px.demultiply();
+            rgb_shifts.from_rgb(px.red(), px.green(), px.blue())
+        }));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tiny-skia")]
+impl<'a> IntoXBuffer<'a> for &'a tiny_skia::Pixmap {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.as_ref().dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        self.as_ref().into_x_buffer(rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        self.as_ref().into_x_buffer_in(rgb_shifts, out)
+    }
+}
+
+#[cfg(feature = "tiny-skia")]
+impl IntoXBuffer<'static> for tiny_skia::Pixmap {
+    type Buffer = XBuffer;
+
+    fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+        self.as_ref().dimensions()
+    }
+
+    fn into_x_buffer(
+        self,
+        rgb_shifts: RgbShifts,
+    ) -> crate::Result<Self::Buffer> {
+        self.as_ref().into_x_buffer(rgb_shifts)
+    }
+
+    fn into_x_buffer_in(
+        self,
+        rgb_shifts: RgbShifts,
+        out: &mut Vec<u32>,
+    ) -> crate::Result {
+        self.as_ref().into_x_buffer_in(rgb_shifts, out)
+    }
+}
+
+#[test]
+#[cfg(feature = "tiny-skia")]
+fn test_tiny_skia() {
+    let mut pixmap = tiny_skia::Pixmap::new(2, 1).unwrap();
+    pixmap.pixels_mut()[0] =
+        tiny_skia::PremultipliedColorU8::from_rgba(64, 32, 0, 128).unwrap();
+    pixmap.pixels_mut()[1] =
+        tiny_skia::PremultipliedColorU8::from_rgba(10, 20, 30, 255).unwrap();
+
+    let xbuf = (&pixmap).into_x_buffer(RgbShifts::XRGB8888).unwrap();
+    assert_eq!(&[0x80_40_00, 0x0A_14_1E], &xbuf[..]);
+    let xbuf = pixmap.into_x_buffer(RgbShifts::XBGR8888).unwrap();
+    assert_eq!(&[0x00_40_80, 0x1E_14_0A], &xbuf[..]);
+}
+
+
+/// Implements [`IntoXBuffer`] for a slice of `rgb` crate pixels paired with
+/// image dimensions.
+///
+/// The slice must hold exactly `width * height` pixels in row-major order.
+/// Otherwise the conversion fails with [`Error::BadBufferSize`].
+#[cfg(feature = "rgb")]
+macro_rules! impl_rgb_slice {
+    ($($Pixel:ident => $Image:ident),* $(,)?) => {$(
+        impl<'a> IntoXBuffer<'a> for (u32, u32, &'a [rgb::$Pixel]) {
+            type Buffer = XBuffer;
+
+            fn dimensions(&self) -> Result<(u16, u16), err::ImageTooLarge> {
+                new_dimensions((self.0, self.1))
+            }
+
+            fn into_x_buffer(
+                self,
+                rgb_shifts: RgbShifts,
+            ) -> crate::Result<Self::Buffer> {
+                XBuffer::from_image(self, rgb_shifts)
+            }
+
+            fn into_x_buffer_in(
+                self,
+                rgb_shifts: RgbShifts,
+                out: &mut Vec<u32>,
+            ) -> crate::Result {
+                let (width, height, pixels) = self;
+                let data = Cow::Borrowed(bytemuck::cast_slice(pixels));
+                $Image::<u8>::new(width, height, data)?
+                    .into_x_buffer_in(rgb_shifts, out)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "rgb")]
+impl_rgb_slice! {
+    RGB8 => RgbImage,
+    RGBA8 => RgbaImage,
+}
+
+#[test]
+#[cfg(feature = "rgb")]
+fn test_rgb_slice() {
+    use rgb::{RGB8, RGBA8};
+
+    let pixels = [RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)];
+    let img = (2, 1, &pixels[..]);
+    assert_eq!(Ok((2, 1)), img.dimensions());
+    let xbuf = img.into_x_buffer(RgbShifts::XRGB8888).unwrap();
+    assert_eq!(&[0x01_02_03, 0x04_05_06], &xbuf[..]);
+
+    let pixels = [RGBA8::new(1, 2, 3, 4)];
+    let xbuf = (1, 1, &pixels[..]).into_x_buffer(RgbShifts::ARGB8888);
+    assert_eq!(&[0x04_01_02_03], &xbuf.unwrap()[..]);
+
+    // Length must match the dimensions.
+    let img = (2, 2, &pixels[..]);
+    assert!(matches!(
+        img.into_x_buffer(RgbShifts::XRGB8888),
+        Err(Error::BadBufferSize(4, 2, 2))
+    ));
+}
@@ -14,34 +14,109 @@
 // setroot.  If not, see <http://www.gnu.org/licenses/>.
 
 
-#![doc = include_str!("../README.md")]
+#![cfg_attr(feature = "image", doc = include_str!("../README.md"))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+#[cfg(feature = "std")]
 use xcb::x::Atom;
+#[cfg(feature = "std")]
 use xcb::{Xid, XidNew, randr, x};
 
 pub mod err;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod img;
+#[cfg(feature = "shm")]
+mod shm;
+#[cfg(feature = "image")]
+mod slideshow;
+#[cfg(feature = "std")]
+mod surface;
+#[cfg(feature = "std")]
+mod wallpaper;
 
 pub use err::Error;
+#[cfg(feature = "image")]
+pub use slideshow::Slideshow;
+#[cfg(feature = "std")]
+use surface::Surface;
+#[cfg(feature = "std")]
+pub use wallpaper::WallpaperBuilder;
 pub type Result<T = (), E = Error> = core::result::Result<T, E>;
 
 /// Handler for an X11 connection.
+#[cfg(feature = "std")]
 pub struct Display {
     conn: xcb::Connection,
     screen_num: i32,
+    /// Name of the display the connection was opened with or `None` if the
+    /// `DISPLAY` environment variable was used.
+    name: Option<String>,
+    /// Cached result of [`Display::randr_version`].
+    randr_version: std::sync::OnceLock<Option<(u32, u32)>>,
 }
 
+#[cfg(feature = "std")]
 impl Display {
     /// Opens connection to X11 display.
-    pub fn open() -> Result<Self> {
-        let (conn, screen_num) = xcb::Connection::connect(None)?;
+    ///
+    /// The display is determined by the `DISPLAY` environment variable.
+    pub fn open() -> Result<Self> { Self::connect(None) }
+
+    /// Opens connection to X11 display with given name, e.g. `":1"`.
+    pub fn open_display(name: &str) -> Result<Self> {
+        Self::connect(Some(name))
+    }
+
+    fn connect(name: Option<&str>) -> Result<Self> {
+        let (conn, screen_num) = xcb::Connection::connect(name)?;
+        let mut display = Self::from_xcb(conn, screen_num)?;
+        display.name = name.map(String::from);
+        Ok(display)
+    }
+
+    /// Re-establishes connection to the X11 display.
+    ///
+    /// Connects to the same display the object was opened with (see
+    /// [`Self::open_display`]) or, if it was opened with [`Self::open`],
+    /// [`Self::from_fd`] or [`Self::from_xcb`], to the display named by the
+    /// `DISPLAY` environment variable.  The screen number is determined anew
+    /// from the display name.  This lets long-running programs recover after
+    /// the X server restarts.
+    ///
+    /// On failure, the object is left unchanged.
+    pub fn reopen(&mut self) -> Result {
+        *self = Self::connect(self.name.as_deref())?;
+        Ok(())
+    }
+
+    /// Opens connection to X11 display over an already open socket.
+    ///
+    /// The file descriptor must be bidirectionally connected to an X server.
+    /// This lets programs which already hold a socket (e.g. passed to them by
+    /// a session manager) reuse it rather than opening a new connection.  No
+    /// authentication information is sent to the server.  `screen_num` is
+    /// validated just like in [`Self::from_xcb`].
+    #[cfg(unix)]
+    pub fn from_fd(fd: std::os::fd::OwnedFd, screen_num: i32) -> Result<Self> {
+        let conn = xcb::Connection::connect_with_fd(fd, None)?;
         Self::from_xcb(conn, screen_num)
     }
 
     /// Constructs the object from existing XCB connection.
     pub fn from_xcb(conn: xcb::Connection, screen_num: i32) -> Result<Self> {
         usize::try_from(screen_num)
-            .map(|_| Self { conn, screen_num })
+            .map(|_| Self {
+                conn,
+                screen_num,
+                name: None,
+                randr_version: Default::default(),
+            })
             .map_err(|_| Error::BadScreenNumber(screen_num))
     }
 
@@ -53,15 +128,31 @@ impl Display {
 
     /// Returns reference to the XCB connection.
     pub fn conn(&self) -> &xcb::Connection { &self.conn }
+
+    /// Sends buffered requests to the X server without waiting for replies.
+    ///
+    /// Requests issued with unchecked methods such as
+    /// [`RootPixmap::put_image_unchecked`] may sit in the connection’s output
+    /// buffer.  This pushes them to the server.  Unlike
+    /// [`RootPixmap::flush`], errors of the requests aren’t checked.
+    pub fn flush(&self) -> Result { self.conn.flush().map_err(Error::from) }
+
     /// Returns the default screen number.
     pub fn default_screen_num(&self) -> i32 { self.screen_num }
     /// Returns the default screen.
     pub fn default_screen(&self) -> Result<&x::Screen, err::BadScreenNumber> {
-        self.conn
-            .get_setup()
-            .roots()
-            .nth(self.screen_num as usize)
-            .ok_or(err::BadScreenNumber(self.screen_num))
+        self.screen(self.screen_num)
+    }
+
+    /// Returns screen with given number.
+    fn screen(
+        &self,
+        screen_num: i32,
+    ) -> Result<&x::Screen, err::BadScreenNumber> {
+        usize::try_from(screen_num)
+            .ok()
+            .and_then(|num| self.conn.get_setup().roots().nth(num))
+            .ok_or(err::BadScreenNumber(screen_num))
     }
 
     /// Returns list of active monitors.
@@ -69,27 +160,272 @@ impl Display {
     /// Uses RandR extensions to query the dimensions of the monitors.  Requires
     /// RandR extension version 1.5 or newer to work.
     pub fn monitors(&self) -> Result<Vec<Monitor>> {
-        let cookie = self.conn.send_request(&randr::GetMonitors {
-            window: self.default_screen()?.root(),
-            get_active: true,
-        });
-        Ok(self
-            .conn
-            .wait_for_reply(cookie)?
+        Ok(self.monitors_iter()?.collect())
+    }
+
+    /// Returns list of active monitors including their rotation.
+    ///
+    /// Works like [`Self::monitors`] but also fills in [`Monitor::rotation`]
+    /// based on rotation of the CRTC driving the first of each monitor’s
+    /// outputs.  Outputs which aren’t connected to any CRTC are skipped.
+    /// Since this takes two more round trips per monitor, the rotation isn’t
+    /// queried by other methods.
+    ///
+    /// Just like [`Self::monitors`], requires RandR extension version 1.5 or
+    /// newer to work.
+    pub fn monitors_with_rotation(&self) -> Result<Vec<Monitor>> {
+        let reply = self.get_monitors()?;
+        Ok(reply
             .monitors()
             .map(|mon| Monitor {
-                name: self.get_atom_name(mon.name()),
-                primary: mon.primary(),
-                x: mon.x(),
-                y: mon.y(),
-                width: mon.width(),
-                height: mon.height(),
-                width_in_millimeters: mon.width_in_millimeters(),
-                height_in_millimeters: mon.height_in_millimeters(),
+                rotation: self.get_monitor_rotation(mon),
+                ..self.new_monitor(mon)
             })
             .collect())
     }
 
+    /// Returns iterator over active monitors.
+    ///
+    /// Works like [`Self::monitors`] but constructs [`Monitor`] objects lazily
+    /// as the iterator advances.  Since constructing each involves further
+    /// queries to the X server (e.g. to get the monitor’s name), this avoids
+    /// unnecessary round trips and allocations when looking for a single
+    /// monitor, e.g. the largest one.
+    ///
+    /// Just like [`Self::monitors`], requires RandR extension version 1.5 or
+    /// newer to work.
+    pub fn monitors_iter(&self) -> Result<impl Iterator<Item = Monitor> + '_> {
+        let reply = self.get_monitors()?;
+        let mut index = 0;
+        // The reply’s iterator borrows the reply so it can’t be stored next
+        // to it.  Instead, skip over already returned monitors each time.
+        // There are only a handful of monitors so this is cheap.
+        Ok(core::iter::from_fn(move || {
+            let mon = reply.monitors().nth(index)?;
+            index += 1;
+            Some(self.new_monitor(mon))
+        }))
+    }
+
+    /// Returns the primary monitor or `None` if no monitor is marked as
+    /// primary.
+    ///
+    /// Just like [`Self::monitors`], requires RandR extension version 1.5 or
+    /// newer to work.
+    pub fn primary_monitor(&self) -> Result<Option<Monitor>> {
+        let reply = self.get_monitors()?;
+        let primary = reply.monitors().find(|mon| mon.primary());
+        Ok(primary.map(|mon| self.new_monitor(mon)))
+    }
+
+    /// Returns version of the RandR extension supported by the X server or
+    /// `None` if the extension isn’t present.
+    ///
+    /// [`Self::monitors`] and related methods require version 1.5 or newer.
+    /// This lets tools decide up front whether to use them or fall back to
+    /// other means of determining the monitor layout (e.g. Xinerama) rather
+    /// than discovering failure mid-operation.  The result is cached so only
+    /// the first call queries the server.
+    ///
+    /// ```no_run
+    /// let display = setroot::Display::open().unwrap();
+    /// let monitors = match display.randr_version().unwrap() {
+    ///     Some(version) if version >= (1, 5) => display.monitors().unwrap(),
+    ///     _ => Vec::new(),
+    /// };
+    /// # let _ = monitors;
+    /// ```
+    pub fn randr_version(&self) -> Result<Option<(u32, u32)>> {
+        if let Some(version) = self.randr_version.get() {
+            return Ok(*version);
+        }
+        let name = randr::XNAME.as_bytes();
+        let cookie = self.conn.send_request(&x::QueryExtension { name });
+        let version = if self.conn.wait_for_reply(cookie)?.present() {
+            let cookie = self.conn.send_request(&randr::QueryVersion {
+                major_version: randr::MAJOR_VERSION,
+                minor_version: randr::MINOR_VERSION,
+            });
+            let reply = self.conn.wait_for_reply(cookie)?;
+            Some((reply.major_version(), reply.minor_version()))
+        } else {
+            None
+        };
+        Ok(*self.randr_version.get_or_init(|| version))
+    }
+
+    /// Returns id of the pixmap currently set as the root window’s background.
+    ///
+    /// The id is read from the `_XROOTPMAP_ID` property of the root window
+    /// which is set by [`RootPixmap::set_background`] and most other tools
+    /// setting the desktop background.  Returns `None` if the property isn’t
+    /// set.
+    pub fn current_root_pixmap(&self) -> Result<Option<x::Pixmap>> {
+        let cookie = self.conn.send_request(&x::InternAtom {
+            only_if_exists: true,
+            name: b"_XROOTPMAP_ID",
+        });
+        let atom = self.conn.wait_for_reply(cookie)?.atom();
+        if atom.is_none() {
+            return Ok(None);
+        }
+        let root = self.default_screen()?.root();
+        Ok(get_pixmap_property(&self.conn, root, atom)?)
+    }
+
+    /// Returns whether a compositing manager is running on the default screen.
+    ///
+    /// Compositing managers often paint the root window themselves in which
+    /// case setting its back pixmap may have no visible effect.  Tools can use
+    /// this method to warn users about that.  The check looks for owner of
+    /// `_NET_WM_CM_S<n>` selection (where `<n>` is the default screen number)
+    /// which compositing managers are required to acquire.
+    pub fn has_compositor(&self) -> Result<bool> {
+        let name = format!("_NET_WM_CM_S{}", self.screen_num);
+        let cookie = self.conn.send_request(&x::InternAtom {
+            only_if_exists: true,
+            name: name.as_bytes(),
+        });
+        let selection = self.conn.wait_for_reply(cookie)?.atom();
+        if selection.is_none() {
+            return Ok(false);
+        }
+        let cookie =
+            self.conn.send_request(&x::GetSelectionOwner { selection });
+        Ok(!self.conn.wait_for_reply(cookie)?.owner().is_none())
+    }
+
+    /// Returns dimensions of the default screen in pixels.
+    pub fn screen_size(&self) -> Result<(u16, u16)> {
+        let screen = self.default_screen()?;
+        Ok((screen.width_in_pixels(), screen.height_in_pixels()))
+    }
+
+    /// Returns RGB shifts which define pixel format used by given screen.
+    ///
+    /// This is the same value [`RootPixmap::rgb_shifts`] returns for a pixmap
+    /// of the screen but doesn’t allocate any resources on the server.  It’s
+    /// useful when preparing images in the server’s format ahead of time, e.g.
+    /// with [`img::MemoryCanvas`], to later draw them with
+    /// [`RootPixmap::put_raw`].
+    ///
+    /// Returns [`Error::BadScreenNumber`] if there’s no screen with given
+    /// number or an error if the screen’s visual isn’t supported.
+    pub fn rgb_shifts_for_screen(
+        &self,
+        screen_num: i32,
+    ) -> Result<img::RgbShifts> {
+        RootPixmap::get_rgb_shifts(self.screen(screen_num)?)
+    }
+
+    /// Returns the largest dimensions of a wallpaper the server accepts.
+    ///
+    /// The size is limited by the maximum screen size the X server supports
+    /// as advertised by the RandR extension.  Since the protocol encodes
+    /// dimensions as 16-bit integers, the result never exceeds
+    /// `(u16::MAX, u16::MAX)`.  Callers can use this to downscale an image
+    /// before converting it rather than have [`RootPixmap::put_image`] fail
+    /// with [`Error::ImageTooLarge`].
+    ///
+    /// Requires RandR extension to work.
+    pub fn max_pixmap_size(&self) -> Result<(u16, u16)> {
+        let cookie = self.conn.send_request(&randr::GetScreenSizeRange {
+            window: self.default_screen()?.root(),
+        });
+        let reply = self.conn.wait_for_reply(cookie)?;
+        Ok((reply.max_width(), reply.max_height()))
+    }
+
+    /// Returns bounding box of all active monitors as `(x, y, width, height)`
+    /// tuple.
+    ///
+    /// The box may start at negative coordinates if monitors are arranged with
+    /// negative offsets.  If there are no active monitors, returns rectangle
+    /// covering the entire default screen.
+    ///
+    /// Just like [`Self::monitors`], requires RandR extension version 1.5 or
+    /// newer to work.
+    pub fn monitors_bounding_box(&self) -> Result<(i16, i16, u16, u16)> {
+        let reply = self.get_monitors()?;
+        let rects = reply
+            .monitors()
+            .map(|mon| (mon.x(), mon.y(), mon.width(), mon.height()));
+        match bounding_box(rects) {
+            Some(rect) => Ok(rect),
+            None => self.screen_size().map(|(w, h)| (0, 0, w, h)),
+        }
+    }
+
+    /// Returns active monitor with given name, e.g. `"DP-2"`, or `None` if
+    /// there’s no such monitor.
+    ///
+    /// The name is compared case-sensitively just like X atom names.  Just
+    /// like [`Self::monitors`], requires RandR extension version 1.5 or newer
+    /// to work.
+    pub fn monitor_by_name(&self, name: &str) -> Result<Option<Monitor>> {
+        Ok(self.monitors_iter()?.find(|mon| mon.name.as_deref() == Some(name)))
+    }
+
+    /// Queries RandR extension for active monitors.
+    fn get_monitors(&self) -> Result<randr::GetMonitorsReply> {
+        let cookie = self.conn.send_request(&randr::GetMonitors {
+            window: self.default_screen()?.root(),
+            get_active: true,
+        });
+        self.conn.wait_for_reply(cookie).map_err(Error::from)
+    }
+
+    /// Constructs [`Monitor`] from RandR monitor description.
+    fn new_monitor(&self, mon: &randr::MonitorInfo) -> Monitor {
+        Monitor {
+            name: self.get_atom_name(mon.name()),
+            primary: mon.primary(),
+            x: mon.x(),
+            y: mon.y(),
+            width: mon.width(),
+            height: mon.height(),
+            width_in_millimeters: mon.width_in_millimeters(),
+            height_in_millimeters: mon.height_in_millimeters(),
+            rotation: img::Rotation::None,
+        }
+    }
+
+    /// Returns rotation of the CRTC driving the first of monitor’s outputs.
+    ///
+    /// Outputs which aren’t connected to any CRTC are skipped.  If no CRTC is
+    /// found or the queries fail, returns [`img::Rotation::None`].
+    fn get_monitor_rotation(&self, mon: &randr::MonitorInfo) -> img::Rotation {
+        let crtc = mon.outputs().iter().find_map(|&output| {
+            let cookie = self.conn.send_request(&randr::GetOutputInfo {
+                output,
+                config_timestamp: x::CURRENT_TIME,
+            });
+            let crtc = self.conn.wait_for_reply(cookie).ok()?.crtc();
+            (!crtc.is_none()).then_some(crtc)
+        });
+        let Some(crtc) = crtc else {
+            return img::Rotation::None;
+        };
+        let cookie = self.conn.send_request(&randr::GetCrtcInfo {
+            crtc,
+            config_timestamp: x::CURRENT_TIME,
+        });
+        let Ok(reply) = self.conn.wait_for_reply(cookie) else {
+            return img::Rotation::None;
+        };
+        // RandR rotates counter-clockwise.
+        let rotation = reply.rotation();
+        if rotation.contains(randr::Rotation::ROTATE_90) {
+            img::Rotation::Cw270
+        } else if rotation.contains(randr::Rotation::ROTATE_180) {
+            img::Rotation::Cw180
+        } else if rotation.contains(randr::Rotation::ROTATE_270) {
+            img::Rotation::Cw90
+        } else {
+            img::Rotation::None
+        }
+    }
+
     /// Returns a representation of the root window’s background pixmap.
     ///
     /// The object can be used to draw on the pixmap before finally setting it
@@ -99,6 +435,193 @@ impl Display {
         RootPixmap::new(self.conn(), self.default_screen()?)
     }
 
+    /// Returns a representation of given screen’s root window’s background
+    /// pixmap.
+    ///
+    /// Works like [`Self::root_pixmap`] but allows targeting screens other
+    /// than the default one on servers with multiple screens (e.g. `:0.1`).
+    /// The pixmap’s dimensions and depth match given screen’s.  Returns
+    /// [`Error::BadScreenNumber`] if there’s no screen with given number.
+    pub fn root_pixmap_for_screen(
+        &self,
+        screen_num: i32,
+    ) -> Result<RootPixmap<'_>> {
+        RootPixmap::new(self.conn(), self.screen(screen_num)?)
+    }
+
+    /// Returns a builder for setting an image as the desktop background of the
+    /// default screen.
+    ///
+    /// The builder lets scale mode, background colour, target monitor and
+    /// blur be configured before the image is drawn and set as the background
+    /// with [`WallpaperBuilder::set`].  See [`WallpaperBuilder`] for defaults
+    /// of each option.
+    pub fn wallpaper(&self) -> WallpaperBuilder<'_> {
+        WallpaperBuilder::new(self)
+    }
+
+    /// Loads image from given file and sets it as the desktop background.
+    ///
+    /// The image is decoded with [`image::open`] and set as the background
+    /// with [`WallpaperBuilder::set`] using default options other than the
+    /// scale `mode`.
+    ///
+    /// Decoding errors are reported as [`Error::Image`].  Note that the crate
+    /// doesn’t enable any image formats in the `image` crate.  Formats which
+    /// should be supported need to be enabled by the application.
+    #[cfg(feature = "image")]
+    pub fn set_wallpaper_from_path(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        mode: img::ScaleMode,
+    ) -> Result {
+        let image = image::open(path)?;
+        self.wallpaper().mode(mode).set(image)
+    }
+
+    /// Sets an image as the desktop background reconnecting to the display
+    /// if the connection was lost.
+    ///
+    /// The image is set as the background with [`WallpaperBuilder::set`] using
+    /// given scale `mode` and `filter` and default values of other options.
+    ///
+    /// If that fails with a connection error (see
+    /// [`Error::is_connection_error`]), e.g. because the X server has been
+    /// restarted, reconnects with [`Self::reopen`] and tries once more.  Other
+    /// errors, including protocol errors, are returned without retrying.
+    pub fn set_background_with_retry<'b>(
+        &mut self,
+        img: impl img::IntoXBuffer<'b> + Clone,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+    ) -> Result {
+        let set = |display: &Self| {
+            display.wallpaper().mode(mode).filter(filter).set(img.clone())
+        };
+        match set(self) {
+            Err(err) if err.is_connection_error() => {
+                self.reopen()?;
+                set(self)
+            }
+            res => res,
+        }
+    }
+
+    /// Sets the same image as the background of every screen of the display.
+    ///
+    /// On servers with multiple screens (e.g. classic ‘Zaphod mode’ dual-head
+    /// configurations), sets the image as background of each screen like
+    /// [`WallpaperBuilder::set`] does for the default screen, using given scale
+    /// `mode` and `filter` and default values of other options.  Since screens
+    /// may use different visuals, the image is converted separately for each
+    /// of them which is why it needs to be cloneable.
+    ///
+    /// All screens are attempted even if some of them fail.  On failure,
+    /// returns errors paired with numbers of the screens they occurred on;
+    /// background of all the other screens has been set successfully.
+    pub fn set_background_all_screens<'b>(
+        &self,
+        img: impl img::IntoXBuffer<'b> + Clone,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+    ) -> Result<(), Vec<(i32, Error)>> {
+        let wallpaper = self.wallpaper().mode(mode).filter(filter);
+        let count = self.conn.get_setup().roots().count() as i32;
+        let errors = (0..count)
+            .filter_map(|num| {
+                let res = wallpaper.clone().screen(num).set(img.clone());
+                res.err().map(|err| (num, err))
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Sets the background of the default screen’s root window to a solid
+    /// colour.
+    ///
+    /// Creates a 1×1 pixmap filled with given colour which is then tiled by the
+    /// X server over the entire root window.  Just like
+    /// [`RootPixmap::set_background`], updates `_XROOTPMAP_ID` and
+    /// `ESETROOT_PMAP_ID` atoms and kills clients holding the old root pixmap.
+    /// This is cheaper than filling a screen-sized [`RootPixmap`].
+    ///
+    /// The pixmap is not freed such that the id stored in the atoms remains
+    /// valid.  Thanks to `RetainTemporary` close-down mode, it outlives the
+    /// connection and is freed once another background is set.
+    pub fn set_solid_background(&self, colour: (u8, u8, u8)) -> Result {
+        let screen = self.default_screen()?;
+        let pixel = RootPixmap::get_rgb_shifts(screen)?
+            .from_rgb(colour.0, colour.1, colour.2);
+
+        let pixmap = self.conn.generate_id::<x::Pixmap>();
+        self.conn.send_and_check_request(&x::CreatePixmap {
+            depth: screen.root_depth(),
+            pid: pixmap,
+            drawable: x::Drawable::Window(screen.root()),
+            width: 1,
+            height: 1,
+        })?;
+        self.fill_pixel(pixmap, pixel)
+            .and_then(|()| {
+                Background { conn: &self.conn, screen, pixmap, pending: None }
+                    .set(true, true, &mut Vec::new())
+            })
+            .inspect_err(|_| {
+                self.conn.send_request(&x::FreePixmap { pixmap });
+            })
+    }
+
+    /// Reads colour of a single pixel of the default screen’s root window.
+    ///
+    /// Since contents of visible child windows are included, this returns
+    /// what’s displayed on the screen at given location which makes it useful
+    /// for implementing colour pickers.  The pixel is decoded using
+    /// [`img::RgbShifts::to_rgb`] with RGB shifts of the screen’s visual.
+    ///
+    /// Returns [`Error::RectOutOfBounds`] if the coordinates lie outside of
+    /// the screen (or an error on protocol error).
+    pub fn sample_pixel(&self, x: i16, y: i16) -> Result<(u8, u8, u8)> {
+        let screen = self.default_screen()?;
+        let rgb_shifts = RootPixmap::get_rgb_shifts(screen)?;
+        let format = PixelFormat::get(&self.conn, screen)?;
+        let dim = (screen.width_in_pixels(), screen.height_in_pixels());
+        let rect = check_rect(x, y, 1, 1, dim)?;
+        let cookie = self.conn.send_request(&x::GetImage {
+            format: x::ImageFormat::ZPixmap,
+            drawable: x::Drawable::Window(screen.root()),
+            x: rect.x,
+            y: rect.y,
+            width: 1,
+            height: 1,
+            plane_mask: !0,
+        });
+        let reply = self.conn.wait_for_reply(cookie)?;
+        let data = reply.data();
+        match format.unpack(data, 1).next() {
+            Some(px) if format.stride(1) == data.len() => {
+                Ok(rgb_shifts.to_rgb(px))
+            }
+            _ => Err(Error::BadBufferSize(data.len(), 1, 1)),
+        }
+    }
+
+    /// Fills a 1×1 pixmap with given pixel value.
+    fn fill_pixel(&self, pixmap: x::Pixmap, pixel: u32) -> Result {
+        let gc = self.conn.generate_id::<x::Gcontext>();
+        self.conn.send_and_check_request(&x::CreateGc {
+            cid: gc,
+            drawable: x::Drawable::Pixmap(pixmap),
+            value_list: &[x::Gc::Foreground(pixel)],
+        })?;
+        let res = self.conn.send_and_check_request(&x::PolyFillRectangle {
+            drawable: x::Drawable::Pixmap(pixmap),
+            gc,
+            rectangles: &[x::Rectangle { x: 0, y: 0, width: 1, height: 1 }],
+        });
+        self.conn.send_request(&x::FreeGc { gc });
+        Ok(res?)
+    }
+
     /// Returns the name of given atom.
     fn get_atom_name(&self, atom: Atom) -> Option<String> {
         let cookie = self.conn.send_request(&x::GetAtomName { atom });
@@ -109,8 +632,41 @@ impl Display {
 }
 
 
+/// A rectangle on a surface, e.g. area covered by an image.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[cfg(feature = "std")]
+impl Rect {
+    /// Returns whether the rectangle has zero area.
+    pub fn is_empty(&self) -> bool { self.width == 0 || self.height == 0 }
+}
+
+#[cfg(feature = "std")]
+impl From<(i16, i16, u16, u16)> for Rect {
+    fn from((x, y, width, height): (i16, i16, u16, u16)) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Rect> for (i16, i16, u16, u16) {
+    fn from(rect: Rect) -> Self { (rect.x, rect.y, rect.width, rect.height) }
+}
+
+
 /// Description of a monitor.
+#[cfg(feature = "std")]
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Monitor {
     pub name: Option<String>,
     pub primary: bool,
@@ -120,32 +676,160 @@ pub struct Monitor {
     pub height: u16,
     pub width_in_millimeters: u32,
     pub height_in_millimeters: u32,
+    /// Rotation of the monitor’s output as set with RandR, expressed as
+    /// clockwise rotation.  Reflections are not reported.
+    ///
+    /// Only [`Display::monitors_with_rotation`] queries the rotation.  Other
+    /// methods leave it as [`img::Rotation::None`].  The monitor’s `width` and
+    /// `height` are already swapped for 90° and 270° rotations.
+    /// [`RootPixmap::put_image_on_monitor`] rotates images by the inverse of
+    /// this rotation.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotation: img::Rotation,
+}
+
+#[cfg(feature = "std")]
+impl Monitor {
+    /// Returns position and dimensions of the monitor as `(x, y, width,
+    /// height)` tuple.
+    pub fn rect(&self) -> (i16, i16, u16, u16) {
+        (self.x, self.y, self.width, self.height)
+    }
+
+    /// Returns whether given point lies on the monitor.
+    ///
+    /// The monitor covers coordinates from `(x, y)` inclusive to `(x + width,
+    /// y + height)` exclusive.  This can be used to find the monitor under the
+    /// mouse cursor.
+    pub fn contains(&self, x: i16, y: i16) -> bool {
+        let contains = |start: i16, len: u16, pos: i16| {
+            (i32::from(start)..i32::from(start) + i32::from(len))
+                .contains(&i32::from(pos))
+        };
+        contains(self.x, self.width, x) && contains(self.y, self.height, y)
+    }
+
+    /// Returns area of the monitor in pixels.
+    pub fn area(&self) -> u32 { u32::from(self.width) * u32::from(self.height) }
+
+    /// Returns horizontal and vertical resolution of the monitor in dots per
+    /// inch.
+    ///
+    /// Returns `None` if physical dimensions of the monitor are unknown, i.e.
+    /// if RandR reports either of them as zero (which it sometimes does, e.g.
+    /// for projectors or virtual machines).
+    pub fn dpi(&self) -> Option<(f32, f32)> {
+        let dpi = |px: u16, mm: u32| {
+            (mm != 0).then(|| f32::from(px) * 25.4 / mm as f32)
+        };
+        Some((
+            dpi(self.width, self.width_in_millimeters)?,
+            dpi(self.height, self.height_in_millimeters)?,
+        ))
+    }
+
+    /// Returns suggested scale factor for the monitor relative to given base
+    /// resolution.
+    ///
+    /// The factor is the monitor’s resolution (average of its horizontal and
+    /// vertical DPI) divided by `base_dpi`, which is typically 96.  HiDPI aware
+    /// programs can use it to pick assets, e.g. ones with `@2x` suffix if the
+    /// factor is close to two.  Returns `None` if the monitor’s DPI is unknown
+    /// (see [`Self::dpi`]) or `base_dpi` isn’t a positive number.
+    pub fn scale_factor(&self, base_dpi: f32) -> Option<f32> {
+        let (x, y) = self.dpi()?;
+        (base_dpi > 0.0 && base_dpi.is_finite())
+            .then(|| (x + y) / 2.0 / base_dpi)
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_monitor_geometry() {
+    let monitor = Monitor {
+        name: None,
+        primary: false,
+        x: -1280,
+        y: -200,
+        width: 1280,
+        height: 1024,
+        width_in_millimeters: 0,
+        height_in_millimeters: 0,
+        rotation: img::Rotation::None,
+    };
+    assert_eq!((-1280, -200, 1280, 1024), monitor.rect());
+    assert_eq!(1280 * 1024, monitor.area());
+    assert!(monitor.contains(-1280, -200));
+    assert!(monitor.contains(-1, 823));
+    assert!(!monitor.contains(0, 0));
+    assert!(!monitor.contains(-1, 824));
+    assert!(!monitor.contains(-1281, 0));
+
+    let monitor =
+        Monitor { x: 32000, y: 0, width: 65535, height: 65535, ..monitor };
+    assert_eq!(65535 * 65535, monitor.area());
+    assert!(monitor.contains(i16::MAX, i16::MAX));
+    assert!(!monitor.contains(31999, 0));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_monitor_dpi() {
+    let monitor = Monitor {
+        name: None,
+        primary: true,
+        x: 0,
+        y: 0,
+        width: 3840,
+        height: 2160,
+        width_in_millimeters: 508,
+        height_in_millimeters: 0,
+        rotation: img::Rotation::Cw90,
+    };
+    assert_eq!(None, monitor.dpi());
+    assert_eq!(None, monitor.scale_factor(96.0));
+
+    let monitor = Monitor { height_in_millimeters: 254, ..monitor };
+    assert_eq!(Some((192.0, 216.0)), monitor.dpi());
+    assert_eq!(Some(2.125), monitor.scale_factor(96.0));
+    assert_eq!(None, monitor.scale_factor(0.0));
+    assert_eq!(None, monitor.scale_factor(f32::NAN));
 }
 
 
 /// A pixmap on a root window scaled to cover the entire screen.  Used to put
 /// images onto it and eventually set as wallpaper.
+#[cfg(feature = "std")]
 pub struct RootPixmap<'a> {
     conn: &'a xcb::Connection,
     screen: &'a x::Screen,
     pixmap: x::Pixmap,
     gc: x::Gcontext,
+    visual: x::Visualid,
     rgb_shifts: img::RgbShifts,
+    format: PixelFormat,
+    /// Requests sent by [`Self::put_image_unchecked`] or when setting the
+    /// background which haven’t been checked by [`Self::flush`] yet.
+    pending: core::cell::RefCell<Vec<xcb::VoidCookieChecked>>,
 }
 
+#[cfg(feature = "std")]
 impl core::ops::Drop for RootPixmap<'_> {
     fn drop(&mut self) {
+        self.discard_pending();
         self.conn.send_request(&x::FreeGc { gc: self.gc });
         self.conn.send_request(&x::FreePixmap { pixmap: self.pixmap });
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> RootPixmap<'a> {
     /// Constructs a new pixmap tied to the screen’s root window and sized to
     /// match screen’s dimensions.
     pub fn new(conn: &'a xcb::Connection, scr: &'a x::Screen) -> Result<Self> {
         // Verify the visual and get R, G and B shifts for later use.
-        let rgb_shifts = Self::get_rgb_shifts(scr)?;
+        let (visual, rgb_shifts) = Self::get_visual(scr)?;
+        let format = PixelFormat::get(conn, scr)?;
 
         let pixmap = conn.generate_id::<x::Pixmap>();
         conn.send_and_check_request(&x::CreatePixmap {
@@ -166,48 +850,77 @@ impl<'a> RootPixmap<'a> {
             conn.send_request(&x::FreePixmap { pixmap });
         })?;
 
-        Ok(Self { conn, screen: scr, pixmap, gc, rgb_shifts })
+        Ok(Self {
+            conn,
+            screen: scr,
+            pixmap,
+            gc,
+            visual,
+            rgb_shifts,
+            format,
+            pending: Default::default(),
+        })
     }
 
     /// Checks that visual is one we support and returns R, G and B channel
     /// sifts.
     fn get_rgb_shifts(scr: &'a x::Screen) -> Result<img::RgbShifts> {
-        fn get_shift(mask: u32) -> Option<u8> {
-            let shift = mask.trailing_zeros();
-            ((mask >> shift) == 0xff).then_some(shift as u8)
-        }
+        Self::get_visual(scr).map(|(_, shifts)| shifts)
+    }
 
+    /// Finds visual of the root depth to use and returns its id and R, G and
+    /// B channel shifts.  See [`choose_visual`].
+    fn get_visual(scr: &'a x::Screen) -> Result<(x::Visualid, img::RgbShifts)> {
         let root_depth = scr.root_depth();
-        let root_visual = scr.root_visual();
-        scr.allowed_depths()
+        let visuals = scr
+            .allowed_depths()
             .filter(|depth| depth.depth() == root_depth)
-            .flat_map(|depth| depth.visuals())
-            .find(|vis| vis.visual_id() == root_visual)
-            .ok_or(Error::CouldNotFindRootVisual(root_visual))
-            .and_then(|vis| {
-                if vis.class() == x::VisualClass::TrueColor &&
-                    (root_depth == 24 || root_depth == 32)
-                {
-                    let shifts = get_shift(vis.red_mask())
-                        .zip(get_shift(vis.green_mask()))
-                        .zip(get_shift(vis.blue_mask()));
-                    if let Some(((r, g), b)) = shifts {
-                        return Ok(img::RgbShifts { r, g, b });
-                    }
-                }
-                Err(Error::UnsupportedVisual(root_depth, vis.class()))
-            })
+            .flat_map(|depth| depth.visuals());
+        choose_visual(visuals, root_depth, scr.root_visual())
     }
 
+    /// Returns width and height of the pixmap.
+    ///
+    /// The pixmap covers the entire screen so this is the same as screen’s
+    /// dimensions in pixels.  Useful when computing custom placement of images
+    /// on the pixmap.
+    pub fn dimensions(&self) -> (u16, u16) { Surface::dimensions(self) }
+
+    /// Returns id of the visual whose masks define pixel format of the pixmap.
+    ///
+    /// This is normally screen’s root visual.  If the server reports root
+    /// visual which isn’t listed among visuals of the root depth, a fallback
+    /// TrueColour or DirectColour visual of that depth is used instead and its
+    /// id is returned.
+    pub fn visual(&self) -> x::Visualid { self.visual }
+
     /// Returns RGB shifts which define pixel format used by the X display.
     ///
     /// The shifts allow converting red, green and blue components into `u32`
     /// value that X server expects.
     pub fn rgb_shifts(&self) -> img::RgbShifts { self.rgb_shifts }
 
+    /// Consumes the object and returns id of the underlying pixmap without
+    /// freeing it.
+    ///
+    /// Normally the pixmap is freed when the object is dropped.  This method
+    /// frees only the graphics context and lets the caller manage lifetime of
+    /// the pixmap, e.g. to hand its id over to another process.  The caller
+    /// becomes responsible for eventually freeing the pixmap with
+    /// [`x::FreePixmap`] request (or retaining it past the connection’s
+    /// lifetime through close-down mode).
+    pub fn into_pixmap(self) -> x::Pixmap {
+        let this = core::mem::ManuallyDrop::new(self);
+        this.discard_pending();
+        this.conn.send_request(&x::FreeGc { gc: this.gc });
+        this.pixmap
+    }
+
     /// Puts an image at given location on the pixmap.
     ///
-    /// The image must be in 24-bit sRGB (that is, 8 bits per subpixel).
+    /// The image must be in 24-bit sRGB (that is, 8 bits per subpixel).  If
+    /// the image has zero width or height, nothing is drawn and no request is
+    /// sent.  This holds for all methods drawing images on the pixmap.
     ///
     /// Returns an error if the dimensions of the image are too large (or on
     /// protocol error).
@@ -217,93 +930,1006 @@ impl<'a> RootPixmap<'a> {
         dst_y: i16,
         img: impl img::IntoXBuffer<'b>,
     ) -> Result {
-        let (width, height) = img.dimensions()?;
-        let buffer = img.into_x_buffer(self.rgb_shifts)?;
-        let buffer = buffer.as_ref();
-        if usize::from(width) * usize::from(height) * 4 == buffer.len() {
-            self.put_raw_impl(dst_x, dst_y, width, height, buffer)
-        } else {
-            Err(Error::BadBufferSize(buffer.len(), width, height))
+        Surface::put_image(self, dst_x, dst_y, img)
+    }
+
+    /// Puts an image at given location on the pixmap without waiting for the
+    /// X server to process the request.
+    ///
+    /// Behaves like [`Self::put_image`] except that it doesn’t round-trip to
+    /// the server.  This reduces latency when putting many images (e.g. tiles
+    /// or per-monitor images) on the pixmap.  Errors detected on the client
+    /// side (such as the image being too large) are still reported
+    /// immediately but protocol errors are reported only by [`Self::flush`]
+    /// which must be called once all the images are sent.  If the pixmap is
+    /// dropped or set as background without flushing, protocol errors of
+    /// unchecked requests are silently ignored.
+    pub fn put_image_unchecked<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let ((width, height), buffer) = self.convert(img)?;
+        self.send_image(dst_x, dst_y, width, height, buffer.as_ref(), |req| {
+            let cookie = self.conn.send_request_checked(req);
+            self.pending.borrow_mut().push(cookie);
+            Ok(())
+        })
+    }
+
+    /// Waits for the X server to process all requests sent by
+    /// [`Self::put_image_unchecked`] and reports the first protocol error
+    /// any of them caused.
+    ///
+    /// This also covers requests which methods such as
+    /// [`Self::set_background`] send without waiting for the server to
+    /// process them (e.g. `KillClient`).
+    ///
+    /// Checking all the requests takes a single round trip to the server.
+    /// Requests sent after the failed one are still checked so that their
+    /// errors don’t linger in the connection.
+    pub fn flush(&self) -> Result {
+        let pending = core::mem::take(&mut *self.pending.borrow_mut());
+        if pending.is_empty() {
+            return Ok(self.conn.flush()?);
+        }
+        let mut res = Ok(());
+        for cookie in pending {
+            let checked = self.conn.check_request(cookie);
+            if res.is_ok() {
+                res = checked.map_err(Error::from);
+            }
         }
+        res
     }
 
-    /// Puts an image at given location on the pixmap.
+    /// Waits for the X server to process all requests sent so far and reports
+    /// the first protocol error any of the pixmap’s requests caused.
     ///
-    /// The image must be in format accepted by the X display server.  This
-    /// format is described by the [`img::RgbShifts`] object returned by
-    /// [`Self::rgb_shifts`] class.
+    /// Methods such as [`Self::set_background`] send some requests without
+    /// waiting for the server to process them.  This forces a round trip to
+    /// the server and checks those requests (see [`Self::flush`]).  Daemons
+    /// can call it after setting the background to confirm success.  Use
+    /// [`Self::set_background_checked`] to get all errors of the atom updates
+    /// instead.
     ///
-    /// Usually, [`Self::put_image`] method is more convenient interface since
-    /// it performs all necessary data conversion to generate format acceptable
-    /// by the display server.
-    #[inline]
-    pub fn put_raw(
+    /// Only errors of requests sent through the pixmap are reported.  Events
+    /// and errors of other requests queued on the connection are left intact
+    /// so the method is safe to use on a connection shared with an event
+    /// loop.
+    pub fn sync(&self) -> Result {
+        if !self.pending.borrow().is_empty() {
+            // Checking the cookies forces the round trip.
+            return self.flush();
+        }
+        let cookie = self.conn.send_request(&x::GetInputFocus {});
+        self.conn.wait_for_reply(cookie)?;
+        Ok(())
+    }
+
+    /// Checks outstanding unchecked requests ignoring any errors.
+    fn discard_pending(&self) {
+        if !self.pending.borrow().is_empty() {
+            let _ = self.flush();
+        }
+    }
+
+    /// Puts an image rotated clockwise by given angle at given location on the
+    /// pixmap.
+    ///
+    /// `dst_x` and `dst_y` specify position of the top-left corner of the
+    /// rotated image.  For [`img::Rotation::Cw90`] and [`img::Rotation::Cw270`]
+    /// the width and height of the drawn image are swapped compared to the
+    /// source image.  This is useful when a monitor is physically rotated but
+    /// the X server isn’t aware of it.
+    ///
+    /// Just like [`Self::put_image`], returns an error if the dimensions of
+    /// the image are too large (or on protocol error).
+    pub fn put_image_rotated<'b>(
         &self,
         dst_x: i16,
         dst_y: i16,
-        width: u16,
-        height: u16,
-        data: &[u32],
+        rotation: img::Rotation,
+        img: impl img::IntoXBuffer<'b>,
     ) -> Result {
-        if usize::from(width) * usize::from(height) == data.len() {
-            let data = bytemuck::must_cast_slice(data);
-            self.put_raw_impl(dst_x, dst_y, width, height, data)
-        } else {
-            Err(Error::BadBufferSize(data.len() * 4, width, height))
-        }
+        Surface::put_image_rotated(self, dst_x, dst_y, rotation, img)
     }
 
-    fn put_raw_impl(
+    /// Puts a blend of two images at given location on the pixmap.
+    ///
+    /// Draws `a * (1 - t) + b * t` computed for each colour component.  With
+    /// `t` equal zero, `a` is drawn; with `t` equal one, `b` is drawn.  Values
+    /// outside of that range are clamped.  This allows rendering transition
+    /// frames of a slideshow.  Blending is done in sRGB colour space.
+    ///
+    /// Both images must have the same dimensions.  Otherwise returns
+    /// [`Error::BadBufferSize`] error with size of `b` buffer and dimensions
+    /// of `a`.
+    pub fn put_crossfade<'b, 'c>(
         &self,
         dst_x: i16,
         dst_y: i16,
-        width: u16,
-        height: u16,
-        data: &[u8],
+        a: impl img::IntoXBuffer<'b>,
+        b: impl img::IntoXBuffer<'c>,
+        t: f32,
     ) -> Result {
-        self.conn
-            .send_and_check_request(&x::PutImage {
-                format: x::ImageFormat::ZPixmap,
-                drawable: x::Drawable::Pixmap(self.pixmap),
-                gc: self.gc,
-                width,
-                height,
-                dst_x,
-                dst_y,
-                left_pad: 0,
-                depth: self.screen.root_depth(),
-                data,
-            })
-            .map_err(Error::from)
+        Surface::put_crossfade(self, dst_x, dst_y, a, b, t)
     }
 
-    /// Set the root pixmap as the background of the root window.
+    /// Blends an image over the current desktop background and draws the
+    /// result on the pixmap.
     ///
-    /// Furthermore, updates `_XROOTPMAP_ID` and `ESETROOT_PMAP_ID` atoms.
+    /// Copies the current background (as published in `_XROOTPMAP_ID` atom)
+    /// onto the pixmap, reads it back with `GetImage` request and draws
+    /// `current * (1 - t) + img * t` over it like [`Self::put_crossfade`]
+    /// does.  If there’s no current background or it can’t be copied, black
+    /// is used instead.  The image is drawn at the top-left corner of the
+    /// pixmap and must fit inside of it.
     ///
-    /// If the method returns an error, the state of the root window is
+    /// Setting the result as background with increasing `t` yields a fade-in
+    /// without a compositor.  A small constant `t` dims the previous
+    /// wallpaper.  Note that blending is done on the CPU and requires reading
+    /// the entire image back from the X server so this is too slow for
+    /// animations with high frame rates.
+    pub fn blend_with_current<'b>(
+        &self,
+        img: impl img::IntoXBuffer<'b>,
+        t: f32,
+    ) -> Result {
+        self.copy_current_background()?;
+        Surface::put_blended(self, 0, 0, img, t)
+    }
+
+    /// Copies the current background (as published in `_XROOTPMAP_ID` atom)
+    /// onto the pixmap.  If there’s no current background or it can’t be
+    /// copied, fills the pixmap with black.
+    fn copy_current_background(&self) -> Result {
+        self.fill_solid(0)?;
+        if let Some(current) = self.current_background() {
+            let (width, height) = self.dimensions();
+            // Errors are ignored since the pixmap may have been freed
+            // already or may have a different depth.  Black is used then.
+            let _ = self.conn.send_and_check_request(&x::CopyArea {
+                src_drawable: x::Drawable::Pixmap(current),
+                dst_drawable: x::Drawable::Pixmap(self.pixmap),
+                gc: self.gc,
+                src_x: 0,
+                src_y: 0,
+                dst_x: 0,
+                dst_y: 0,
+                width,
+                height,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns pixmap currently published as the desktop background in
+    /// `_XROOTPMAP_ID` atom or `None` if there isn’t one.
+    fn current_background(&self) -> Option<x::Pixmap> {
+        let cookie = self.conn.send_request(&x::InternAtom {
+            only_if_exists: true,
+            name: b"_XROOTPMAP_ID",
+        });
+        let atom = self.conn.wait_for_reply(cookie).ok()?.atom();
+        if atom.is_none() {
+            return None;
+        }
+        get_pixmap_property(self.conn, self.screen.root(), atom).ok()?
+    }
+
+    /// Converts an image into the pixmap’s pixel format ahead of time.
+    ///
+    /// The returned image can be drawn with [`Self::put_prepared`] any number
+    /// of times without converting it again.  Since the conversion depends on
+    /// the display’s pixel format (see [`Self::rgb_shifts`]), the prepared
+    /// image should only be drawn on pixmaps of the same display and screen.
+    /// See [`img::PreparedImage`].
+    ///
+    /// Returns an error if the dimensions of the image are too large.
+    pub fn prepare<'b>(
+        &self,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<img::PreparedImage> {
+        img::PreparedImage::new(img, self.rgb_shifts)
+    }
+
+    /// Puts a prepared image at given location on the pixmap.
+    ///
+    /// Works like [`Self::put_image`] but skips the conversion if the image
+    /// has been prepared for this pixmap’s pixel format.  See
+    /// [`Self::prepare`].
+    pub fn put_prepared(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: &img::PreparedImage,
+    ) -> Result {
+        Surface::put_image(self, dst_x, dst_y, img)
+    }
+
+    /// Puts an image at given location on the pixmap reusing a scratch buffer.
+    ///
+    /// Behaves like [`Self::put_image`] but converts the image into `scratch`
+    /// buffer rather than allocating a new one.  This is useful when drawing
+    /// images repeatedly (e.g. in a slideshow) since the allocation can be
+    /// hoisted out of the loop.
+    pub fn put_image_reusing<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+        scratch: &mut Vec<u32>,
+    ) -> Result {
+        Surface::put_image_reusing(self, dst_x, dst_y, img, scratch)
+    }
+
+    /// Puts an image on the pixmap scaling it according to given mode reusing
+    /// a scratch buffer.
+    ///
+    /// Behaves like [`Self::put_image_scaled`] but converts the image into
+    /// `scratch` buffer like [`Self::put_image_reusing`] does.
+    pub fn put_image_scaled_reusing<'b>(
+        &self,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+        scratch: &mut Vec<u32>,
+    ) -> Result<Rect> {
+        Surface::put_image_scaled_reusing(self, mode, filter, img, scratch)
+    }
+
+    /// Puts an image at given location on the pixmap using shared memory.
+    ///
+    /// Behaves like [`Self::put_image`] but transfers the image to the X
+    /// display server via a shared memory segment using MIT-SHM extension
+    /// which is faster for large images.  If the extension isn’t available or
+    /// the segment cannot be shared with the server (e.g. because it runs on
+    /// a remote machine), falls back to sending the image over the socket.
+    #[cfg(feature = "shm")]
+    pub fn put_image_shm<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let ((width, height), buffer) = self.convert(img)?;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let data = self.format.pack(buffer.as_ref(), width);
+        let Some(mut segment) = shm::Segment::new(self.conn, data.len()) else {
+            return self.put_raw_impl(
+                dst_x,
+                dst_y,
+                width,
+                height,
+                buffer.as_ref(),
+            );
+        };
+        segment.as_mut_slice().copy_from_slice(&data);
+        self.conn
+            .send_and_check_request(&xcb::shm::PutImage {
+                drawable: x::Drawable::Pixmap(self.pixmap),
+                gc: self.gc,
+                total_width: width,
+                total_height: height,
+                src_x: 0,
+                src_y: 0,
+                src_width: width,
+                src_height: height,
+                dst_x,
+                dst_y,
+                depth: self.screen.root_depth(),
+                format: x::ImageFormat::ZPixmap as u8,
+                send_event: false,
+                shmseg: segment.seg(),
+                offset: 0,
+            })
+            .map_err(Error::from)
+    }
+
+    /// Puts an image on the pixmap scaling it according to given mode.
+    ///
+    /// The image is scaled such that it matches the dimensions of the pixmap
+    /// (i.e. the entire screen) as described by [`img::ScaleMode`].  The
+    /// resizing uses given `filter`; note that filters other than
+    /// [`img::Filter::Nearest`] require `resize` feature.  With
+    /// [`img::ScaleMode::Fit`] and [`img::ScaleMode::Center`] modes, parts of
+    /// the pixmap may be left untouched.
+    ///
+    /// Returns the rectangle of the pixmap the image was drawn onto.  This
+    /// lets the caller paint the remaining area, e.g. with
+    /// [`Self::fill_rect`].  With [`img::ScaleMode::Tile`] and
+    /// [`img::ScaleMode::Fill`] modes, it’s the entire pixmap.
+    ///
+    /// Just like [`Self::put_image`], returns an error if the dimensions of
+    /// the image are too large (or on protocol error).
+    pub fn put_image_scaled<'b>(
+        &self,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        Surface::put_image_scaled(self, mode, filter, img)
+    }
+
+    /// Puts an image on the pixmap scaling it according to given mode and
+    /// anchoring it according to given gravity.
+    ///
+    /// Works like [`Self::put_image_scaled`] (which uses
+    /// [`img::Gravity::Center`]) except that with [`img::ScaleMode::Fill`],
+    /// [`img::ScaleMode::Fit`] and [`img::ScaleMode::Center`] modes the image
+    /// is aligned with the side or corner of the pixmap given by `gravity`.
+    /// For example, with `Center` mode and [`img::Gravity::SouthEast`],
+    /// a logo is drawn unscaled in the bottom-right corner of the screen.
+    /// `gravity` is ignored in the remaining modes.
+    pub fn put_image_scaled_with_gravity<'b>(
+        &self,
+        mode: img::ScaleMode,
+        gravity: img::Gravity,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        Surface::put_image_scaled_with_gravity(self, mode, gravity, filter, img)
+    }
+
+    /// Puts an image on the part of the pixmap covered by given monitor
+    /// scaling it according to given mode.
+    ///
+    /// Works like [`Self::put_image_scaled`] except that the image is fitted
+    /// into the monitor’s rectangle rather than the entire pixmap.  With
+    /// [`img::ScaleMode::Tile`], tiles are aligned with the monitor’s top-left
+    /// corner and clipped to the monitor’s rectangle such that a small texture
+    /// can be repeated at native density of each monitor.
+    ///
+    /// If the monitor is rotated (see [`Monitor::rotation`]), the image is
+    /// first rotated by the inverse rotation and then scaled to the monitor’s
+    /// rectangle.  Use [`Display::monitors_with_rotation`] to get monitors
+    /// with their rotation filled in.
+    ///
+    /// Returns the rectangle of the pixmap the image was drawn onto.
+    pub fn put_image_on_monitor<'b>(
+        &self,
+        monitor: &Monitor,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        let rotation = monitor.rotation.inverse();
+        let area = monitor.rect();
+        Surface::put_image_rotated_in(self, area, rotation, mode, filter, img)
+    }
+
+    /// Composites multiple images onto the pixmap in a single operation.
+    ///
+    /// Layers are drawn in order, i.e. later layers end up on top of earlier
+    /// ones.  Each pixel is blended with what’s underneath according to the
+    /// layer’s opacity multiplied by the image’s alpha channel (images without
+    /// alpha channel are opaque).  Parts of layers outside of the pixmap are
+    /// clipped and fully transparent layers are skipped.
+    ///
+    /// The area covered by the layers is read from the server, blended in
+    /// memory and sent back in one go.  This is useful for overlaying a logo
+    /// on a photo without intermediate states being visible.
+    ///
+    /// Returns an error if dimensions of any of the images are too large (or
+    /// on protocol error).
+    pub fn composite<'b, I: img::IntoXBuffer<'b> + Clone>(
+        &self,
+        layers: &[img::Layer<I>],
+    ) -> Result {
+        Surface::composite(self, layers)
+    }
+
+    /// Spans a single image across multiple monitors.
+    ///
+    /// The image is scaled according to `mode` to the bounding box of all
+    /// given monitors (see [`Display::monitors`]) and each monitor shows the
+    /// slice of it which corresponds to the monitor’s position in the layout.
+    /// This way a panoramic photo appears continuous across physically
+    /// adjacent screens.  Parts of the image falling into gaps between
+    /// monitors aren’t drawn.  With [`img::ScaleMode::Tile`], tiles are
+    /// aligned with the bounding box’s top-left corner.
+    ///
+    /// This differs from calling [`Self::put_image_on_monitor`] for each
+    /// monitor which would show an independently scaled copy of the entire
+    /// image on each of them.  Does nothing if `monitors` is empty.
+    pub fn put_image_spanned<'b>(
+        &self,
+        monitors: &[Monitor],
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let areas = monitors.iter().map(Monitor::rect).collect::<Vec<_>>();
+        Surface::put_image_spanned(self, &areas, mode, filter, img)
+    }
+
+    /// Puts an image on the pixmap scaled to fit over a blurred copy of it
+    /// scaled to cover the entire pixmap.
+    ///
+    /// This is the popular ‘blurred fill’ effect which avoids bars around an
+    /// image whose aspect ratio doesn’t match the screen’s.  The background
+    /// copy is scaled as in [`img::ScaleMode::Fill`] mode and blurred with
+    /// a box blur of given `radius`; the image is then drawn on top of it as
+    /// in [`img::ScaleMode::Fit`] mode.  Both are resized with given `filter`.
+    /// Larger radius produces stronger blur but takes longer to compute.
+    ///
+    /// Just like [`Self::put_image`], returns an error if the dimensions of
+    /// the image are too large (or on protocol error).
+    pub fn put_image_blurred_fit<'b>(
+        &self,
+        radius: u16,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        Surface::put_image_blurred_fit(self, radius, filter, img)
+    }
+
+    /// Puts an image at given location on the pixmap.
+    ///
+    /// The image must be in format accepted by the X display server.  This
+    /// format is described by the [`img::RgbShifts`] object returned by
+    /// [`Self::rgb_shifts`] class.
+    ///
+    /// Usually, [`Self::put_image`] method is more convenient interface since
+    /// it performs all necessary data conversion to generate format acceptable
+    /// by the display server.
+    #[inline]
+    pub fn put_raw(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u32],
+    ) -> Result {
+        Surface::put_raw(self, dst_x, dst_y, width, height, data)
+    }
+
+    /// Puts an image given as bytes at given location on the pixmap.
+    ///
+    /// Works like [`Self::put_raw`] except that pixels are given as a byte
+    /// buffer in host’s byte order, e.g. one returned by
+    /// [`img::IntoXBuffer::into_x_buffer`].  This avoids copying the buffer
+    /// just to get a `&[u32]` slice.  `data` must hold exactly four bytes for
+    /// each pixel.  Otherwise returns [`Error::BadBufferSize`] error.
+    pub fn put_raw_bytes(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> Result {
+        Surface::put_raw_bytes(self, dst_x, dst_y, width, height, data)
+    }
+
+    /// Repeats an image across the entire pixmap.
+    ///
+    /// One of the tiles is placed with its top-left corner at `(offset_x,
+    /// offset_y)` and the rest are arranged around it such that they cover
+    /// the whole pixmap.  Tiles which fall partially outside of the pixmap are
+    /// clipped.  The image is converted into the X display server format only
+    /// once.
+    ///
+    /// Just like [`Self::put_image`], returns an error if the dimensions of
+    /// the image are too large (or on protocol error).
+    pub fn put_image_tiled<'b>(
+        &self,
+        offset_x: i16,
+        offset_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        Surface::put_image_tiled(self, offset_x, offset_y, img)
+    }
+
+    /// Repeats an image across the entire pixmap mirroring every other copy.
+    ///
+    /// Works like [`Self::put_image_tiled`] except that horizontally adjacent
+    /// copies of the image are mirror images of each other and so are
+    /// vertically adjacent ones.  This way edges of the copies meet
+    /// continuously and no seams are visible even if the image isn’t
+    /// a seamless texture.  The copy at `(offset_x, offset_y)` isn’t mirrored.
+    ///
+    /// Returns an error if twice the dimensions of the image are too large (or
+    /// on protocol error).
+    pub fn put_image_tiled_mirrored<'b>(
+        &self,
+        offset_x: i16,
+        offset_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        Surface::put_image_tiled_mirrored(self, offset_x, offset_y, img)
+    }
+
+    /// Fills the entire pixmap with a solid colour.
+    ///
+    /// The colour must be in format accepted by the X display server (just
+    /// like pixels passed to [`Self::put_raw`]).  [`img::RgbShifts::from_rgb`]
+    /// method of the object returned by [`Self::rgb_shifts`] can be used to
+    /// construct it.
+    ///
+    /// This is useful before drawing an image which doesn’t cover the entire
+    /// pixmap, e.g. when using [`img::ScaleMode::Fit`] or
+    /// [`img::ScaleMode::Center`] modes.
+    pub fn fill_solid(&self, colour: u32) -> Result {
+        Surface::fill_solid(self, colour)
+    }
+
+    /// Fills given rectangle of the pixmap with a solid colour.
+    ///
+    /// The colour must be in format accepted by the X display server just like
+    /// in [`Self::fill_solid`].  This can be used to paint bars around an image
+    /// drawn with [`img::ScaleMode::Fit`] mode without drawing over it.
+    ///
+    /// Returns an error if the rectangle doesn’t fit inside of the pixmap (or
+    /// on protocol error).
+    pub fn fill_rect(
+        &self,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        colour: u32,
+    ) -> Result {
+        Surface::fill_rect(self, x, y, width, height, colour)
+    }
+
+    /// Fills the entire pixmap with a checkerboard of `cell`×`cell` squares.
+    ///
+    /// Squares alternate between colours `a` and `b` starting with `a` in the
+    /// top-left corner.  Squares at the right and bottom edges are cut short
+    /// if the pixmap’s dimensions aren’t multiples of `cell`.  The colours
+    /// must be in format accepted by the X display server just like in
+    /// [`Self::fill_solid`].
+    ///
+    /// The pattern is drawn without allocating an image buffer which makes it
+    /// handy for minimalist setups and for checking that images are placed
+    /// pixel-exactly.
+    ///
+    /// Returns an error if `cell` is zero (or on protocol error).
+    pub fn fill_checkerboard(&self, a: u32, b: u32, cell: u16) -> Result {
+        Surface::fill_checkerboard(self, a, b, cell)
+    }
+
+    /// Fills the entire pixmap with a linear gradient.
+    ///
+    /// The colour changes from `start` to `end` in given direction.  The
+    /// colours are interpolated in sRGB space, i.e. without gamma correction,
+    /// which may result in mid-tones appearing darker than expected.
+    ///
+    /// Smooth gradients may show visible bands on displays with 8-bit
+    /// channels.  [`img::Dither::Ordered`] hides them by spreading the
+    /// quantisation error in a regular pattern at the cost of slower
+    /// rendering.
+    pub fn fill_linear_gradient(
+        &self,
+        start: (u8, u8, u8),
+        end: (u8, u8, u8),
+        direction: img::GradientDirection,
+        dither: img::Dither,
+    ) -> Result {
+        Surface::fill_linear_gradient(self, start, end, direction, dither)
+    }
+
+    /// Changes values of the graphics context used for drawing on the pixmap.
+    ///
+    /// Sends `ChangeGc` request with given values.  This lets advanced users
+    /// set e.g. a clip mask (see [`x::Gc::ClipMask`] and
+    /// [`x::SetClipRectangles`] request) such that subsequent drawing is
+    /// restricted to a non-rectangular region.  The values persist for all
+    /// following draws until changed again.  For example, a clip mask can be
+    /// removed with `x::Gc::ClipMask(x::Pixmap::none())`.
+    ///
+    /// Note that drawing methods overwrite values they depend on.  In
+    /// particular, the foreground colour is set by [`Self::fill_rect`] and
+    /// similar methods and the font by [`Self::draw_text`].
+    ///
+    /// Values must be distinct and sorted in the order of the [`x::Gc`]
+    /// variants.  Otherwise `xcb` panics.
+    ///
+    /// Returns an error on protocol error, e.g. if the values are invalid.
+    pub fn with_gc_values(&self, values: &[x::Gc]) -> Result {
+        self.conn
+            .send_and_check_request(&x::ChangeGc {
+                gc: self.gc,
+                value_list: values,
+            })
+            .map_err(Error::from)
+    }
+
+    /// Draws a line of text on the pixmap using a core X font.
+    ///
+    /// `font` is name of the font as understood by the X server, e.g.
+    /// `"fixed"` or an XLFD pattern such as
+    /// `"-*-helvetica-bold-r-*-*-24-*-*-*-*-*-iso8859-1"`.  `(x, y)` is the
+    /// position of the left end of the text’s baseline and `colour` must be in
+    /// format accepted by the X display server (see [`Self::fill_solid`]).
+    ///
+    /// The text is drawn with `ImageText8` request which fills the text’s
+    /// bounding box with the graphics context’s background colour.  It can be
+    /// changed with [`Self::with_gc_values`], e.g.:
+    ///
+    /// ```no_run
+    /// # let display = setroot::Display::open().unwrap();
+    /// # let pixmap = display.root_pixmap().unwrap();
+    /// let black = pixmap.rgb_shifts().from_rgb(0u8, 0, 0);
+    /// let white = pixmap.rgb_shifts().from_rgb(255u8, 255, 255);
+    /// pixmap.with_gc_values(&[xcb::x::Gc::Background(black)]).unwrap();
+    /// pixmap.draw_text(10, 20, "fixed", white, "hostname").unwrap();
+    /// ```
+    ///
+    /// This is meant for simple labels such as host name and avoids the need
+    /// for a text shaping library.  Only ASCII is supported; other characters
+    /// are drawn as question marks.  Text longer than 255 characters is
+    /// truncated.
+    ///
+    /// Returns an error if the font can’t be opened (or on protocol error).
+    pub fn draw_text(
+        &self,
+        x: i16,
+        y: i16,
+        font: &str,
+        colour: u32,
+        text: &str,
+    ) -> Result {
+        let fid: x::Font = self.conn.generate_id();
+        let open = self
+            .conn
+            .send_request_checked(&x::OpenFont { fid, name: font.as_bytes() });
+        let change = self.conn.send_request_checked(&x::ChangeGc {
+            gc: self.gc,
+            value_list: &[x::Gc::Foreground(colour), x::Gc::Font(fid)],
+        });
+        // The GC keeps a reference to the font so it can be closed right
+        // away.  It’s freed once the GC stops using it.
+        let close = self.conn.send_request_checked(&x::CloseFont { font: fid });
+        let draw = self.conn.send_request_checked(&x::ImageText8 {
+            drawable: x::Drawable::Pixmap(self.pixmap),
+            gc: self.gc,
+            x,
+            y,
+            string: &text_bytes(text),
+        });
+        // All requests are checked in a single round trip.  Each cookie is
+        // checked so that no error is left pending but only the first error
+        // is returned.
+        let mut res = Ok(());
+        for cookie in [open, change, close, draw] {
+            res = res.and(self.conn.check_request(cookie));
+        }
+        Ok(res?)
+    }
+
+    /// Reads a rectangle of the pixmap as an RGB image.
+    ///
+    /// The pixels are converted from the format used by the X display server
+    /// using [`img::RgbShifts::to_rgb`].  Returns an error if the rectangle
+    /// doesn’t fit inside of the pixmap (or on protocol error).
+    pub fn get_image(
+        &self,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<img::RgbImage<'static, u8>> {
+        let rect = check_rect(x, y, width, height, self.dimensions())?;
+        let data = self
+            .get_raw_impl(rect)?
+            .into_iter()
+            .flat_map(|px| {
+                let (r, g, b) = self.rgb_shifts.to_rgb(px);
+                [r, g, b]
+            })
+            .collect::<Vec<u8>>();
+        img::RgbImage::new(width.into(), height.into(), data.into())
+    }
+
+    /// Set the root pixmap as the background of the root window.
+    ///
+    /// Furthermore, updates `_XROOTPMAP_ID` and `ESETROOT_PMAP_ID` atoms.
+    /// This is what pseudo-transparent programs expect and suits most window
+    /// managers.  Use [`Self::set_background_plain`] on desktops which get
+    /// confused by the atoms.
+    ///
+    /// If the method returns an error, the state of the root window is
     /// unspecified.  For example, it’s possible that the atoms were updated
     /// (such that applications using pseudo translucency will use the new
     /// background) but the actual background of the root window has not been
     /// updated.
     ///
-    /// Furthermore, the method ignores some errors so long as the back pixmap
-    /// of the root window is set.  This may result in redrawing artefacts.
+    /// Furthermore, the method ignores errors which occur when updating the
+    /// atoms so long as the back pixmap of the root window is set.  This may
+    /// result in redrawing artefacts or pseudo transparency not working.  Use
+    /// [`Self::set_background_checked`] to have those errors reported.
+    ///
+    /// To make sure old root pixmaps don’t leak, the method kills clients
+    /// owning previous root pixmap and all clients whose resources were
+    /// retained with `RetainTemporary` close-down mode.  It then sets the
+    /// close-down mode of this connection to `RetainTemporary` so that the root
+    /// pixmap survives the connection being closed.  This is what `Esetroot`
+    /// and similar programs do.  Use [`Self::set_background_keep_clients`] to
+    /// avoid killing any clients.
     pub fn set_background(&self) -> Result {
-        self.set_root_atoms();
+        self.background().set(true, true, &mut Vec::new())
+    }
 
-        self.conn.send_request(&x::KillClient {
-            resource: 0, // AllTemporary
-        });
-        self.conn.send_request(&x::SetCloseDownMode {
-            mode: x::CloseDown::RetainTemporary,
+    /// Set the root pixmap as the background of the root window without
+    /// killing any clients.
+    ///
+    /// Works like [`Self::set_background`] except that it doesn’t issue
+    /// `KillClient` requests and doesn’t change the close-down mode of the
+    /// connection.  This avoids disrupting clients which legitimately hold
+    /// temporary resources but means that pixmaps of previously set
+    /// backgrounds (as set by other programs) may leak in the X server.
+    /// Furthermore, since the close-down mode is not changed, the pixmap id
+    /// stored in the atoms becomes invalid once the connection is closed.
+    pub fn set_background_keep_clients(&self) -> Result {
+        self.background().set(false, true, &mut Vec::new())
+    }
+
+    /// Set the root pixmap as the background of the root window without
+    /// clearing the window.
+    ///
+    /// Works like [`Self::set_background`] except that it doesn’t issue
+    /// `ClearArea` request after changing the root window’s back pixmap.
+    /// Clearing repaints the entire root window at once which on some setups
+    /// shows as a brief flash.  Without it, the X server only draws the new
+    /// background in areas which get exposed (e.g. when a window is moved or
+    /// unmapped) so the old background remains visible elsewhere until then.
+    ///
+    /// This is useful for callers which trigger exposure some other way or
+    /// which run under a compositor which draws the desktop background from
+    /// the `_XROOTPMAP_ID` atom rather than from the root window.
+    pub fn set_background_no_clear(&self) -> Result {
+        self.background().set(true, false, &mut Vec::new())
+    }
+
+    /// Set the root pixmap as the background of the root window without
+    /// publishing it in the root atoms.
+    ///
+    /// Works like [`Self::set_background`] except that `_XROOTPMAP_ID` and
+    /// `ESETROOT_PMAP_ID` atoms are left untouched, no clients are killed and
+    /// the close-down mode of the connection is not changed.  This mirrors
+    /// what `xsetroot` does.  The X server keeps the back pixmap of the root
+    /// window alive even after the pixmap is freed so the background persists
+    /// once the object is dropped or the connection is closed.
+    ///
+    /// Desktops whose file manager draws the desktop itself, such as GNOME
+    /// with Nautilus, may react to the atoms changing by repainting or
+    /// resetting the background.  This method avoids fighting with them.
+    /// On the other hand, pseudo-transparent terminals and panels (which
+    /// read the atoms) keep showing the previous background.  For plain
+    /// window managers such as Openbox, Fluxbox or i3, prefer
+    /// [`Self::set_background`].
+    pub fn set_background_plain(&self) -> Result {
+        self.background().set_back_pixmap(true)
+    }
+
+    /// Set the root pixmap as the background of the root window reporting all
+    /// errors.
+    ///
+    /// Works like [`Self::set_background`] except that failures to update the
+    /// `_XROOTPMAP_ID` and `ESETROOT_PMAP_ID` atoms are not ignored.  All the
+    /// steps are attempted even if some of them fail and all the errors which
+    /// occurred are returned.  Errors related to the atoms, including failures
+    /// to change the properties (e.g. when the client is restricted by
+    /// a security policy), are reported as [`Error::AtomUpdate`] or
+    /// [`Error::CouldNotInternAtom`] which include name of the affected atom.
+    ///
+    /// As with `set_background`, if the method returns an error, the state of
+    /// the root window is unspecified.
+    pub fn set_background_checked(&self) -> Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+        if let Err(err) = self.background().set(true, true, &mut errors) {
+            errors.push(err);
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Publishes the root pixmap for pseudo transparency without setting it as
+    /// the background of the root window.
+    ///
+    /// Updates `_XROOTPMAP_ID` and `ESETROOT_PMAP_ID` atoms such that programs
+    /// which use them to fake transparency see the new pixmap but, unlike
+    /// [`Self::set_background`], doesn’t change the back pixmap of the root
+    /// window.  This is useful with compositors which draw the desktop
+    /// background themselves.
+    ///
+    /// Just like `set_background`, kills clients holding old root pixmaps and
+    /// sets `RetainTemporary` close-down mode.  All steps are attempted and
+    /// all errors which occurred are returned.
+    pub fn set_pseudo_transparency_atoms(&self) -> Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+        self.background().set_atoms(true, &mut errors);
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Returns helper for setting the pixmap as the root window’s background.
+    fn background(&self) -> Background<'_> {
+        Background {
+            conn: self.conn,
+            screen: self.screen,
+            pixmap: self.pixmap,
+            pending: Some(&self.pending),
+        }
+    }
+
+    /// Sends an image in format accepted by the X display server using `send`
+    /// to issue `PutImage` requests.
+    ///
+    /// If the image doesn’t fit in a single request, splits it into bands of
+    /// whole scanlines which are sent in separate requests.  If even a single
+    /// scanline is too long, fails with [`Error::RequestTooLarge`] without
+    /// sending anything.
+    ///
+    /// On 16-bit visuals, the pixels are first packed into two bytes each.  If
+    /// the server’s image byte order differs from the host’s, bytes of each
+    /// pixel are swapped.  Scanlines are padded as required by the server.
+    fn send_image(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+        send: impl Fn(&x::PutImage<'_>) -> Result,
+    ) -> Result {
+        // Some servers reject PutImage requests of empty images.
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let data = self.format.pack(data, width);
+        let max_len = self.conn.get_maximum_request_length() as usize * 4;
+        let stride = self.format.stride(width);
+        for (row, rows) in split_rows(stride, height, max_len)? {
+            let start = usize::from(row) * stride;
+            let end = start + usize::from(rows) * stride;
+            send(&x::PutImage {
+                format: x::ImageFormat::ZPixmap,
+                drawable: x::Drawable::Pixmap(self.pixmap),
+                gc: self.gc,
+                width,
+                height: rows,
+                dst_x,
+                dst_y: dst_y.wrapping_add_unsigned(row),
+                left_pad: 0,
+                depth: self.screen.root_depth(),
+                data: &data[start..end],
+            })?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(feature = "std")]
+impl Surface for RootPixmap<'_> {
+    fn dimensions(&self) -> (u16, u16) {
+        (self.screen.width_in_pixels(), self.screen.height_in_pixels())
+    }
+
+    fn rgb_shifts(&self) -> img::RgbShifts { self.rgb_shifts }
+
+    /// Puts an image in format accepted by the X display server.
+    fn put_raw_impl(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> Result {
+        self.send_image(dst_x, dst_y, width, height, data, |req| {
+            Ok(self.conn.send_and_check_request(req)?)
+        })
+    }
+
+    /// Fills given rectangles with a solid colour.
+    ///
+    /// Rectangles are batched into as few `PolyFillRectangle` requests as
+    /// maximum request length allows.
+    fn fill_rects_impl(&self, rects: &[x::Rectangle], colour: u32) -> Result {
+        self.conn.send_and_check_request(&x::ChangeGc {
+            gc: self.gc,
+            value_list: &[x::Gc::Foreground(colour)],
+        })?;
+        // Request header takes 12 bytes and each rectangle 8 bytes.
+        let max_len = self.conn.get_maximum_request_length() as usize * 4;
+        for chunk in rects.chunks((max_len - 12) / 8) {
+            self.conn.send_and_check_request(&x::PolyFillRectangle {
+                drawable: x::Drawable::Pixmap(self.pixmap),
+                gc: self.gc,
+                rectangles: chunk,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Reads pixels in given rectangle using `GetImage` request.
+    fn get_raw_impl(&self, rect: x::Rectangle) -> Result<Vec<u32>> {
+        let cookie = self.conn.send_request(&x::GetImage {
+            format: x::ImageFormat::ZPixmap,
+            drawable: x::Drawable::Pixmap(self.pixmap),
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+            plane_mask: !0,
         });
+        let reply = self.conn.wait_for_reply(cookie)?;
+        let data = reply.data();
+        let (width, height) = (rect.width, rect.height);
+        if self.format.stride(width) * usize::from(height) != data.len() {
+            return Err(Error::BadBufferSize(data.len(), width, height));
+        }
+        Ok(self.format.unpack(data, width).collect())
+    }
+}
 
+
+/// A pixmap about to be set as the background of a root window.
+///
+/// Shares code for updating the root atoms and back pixmap between
+/// [`RootPixmap`] and [`Display::set_solid_background`].
+#[cfg(feature = "std")]
+struct Background<'a> {
+    conn: &'a xcb::Connection,
+    screen: &'a x::Screen,
+    pixmap: x::Pixmap,
+    /// Where to store cookies of requests which aren’t waited for.  If
+    /// `None`, such requests are sent unchecked.
+    pending: Option<&'a core::cell::RefCell<Vec<xcb::VoidCookieChecked>>>,
+}
+
+#[cfg(feature = "std")]
+impl Background<'_> {
+    /// Sends a request without waiting for the X server to process it.
+    ///
+    /// If `self.pending` is set, the request is checked and its cookie stored
+    /// there for [`RootPixmap::flush`] to check later.  Otherwise, the request
+    /// is unchecked and its error, if any, is delivered as an event.
+    fn send<R: xcb::RequestWithoutReply>(&self, req: &R) {
+        if let Some(pending) = self.pending {
+            let cookie = self.conn.send_request_checked(req);
+            pending.borrow_mut().push(cookie);
+        } else {
+            self.conn.send_request(req);
+        }
+    }
+
+    /// Updates the root atoms, sets the pixmap as the back pixmap of the root
+    /// window and, if `clear` is true, clears the window so that the new
+    /// background is drawn.
+    ///
+    /// Errors encountered when updating the atoms are appended to `errors`.
+    /// If `kill_clients` is true, kills clients holding old root pixmaps and
+    /// sets `RetainTemporary` close-down mode.
+    fn set(
+        &self,
+        kill_clients: bool,
+        clear: bool,
+        errors: &mut Vec<Error>,
+    ) -> Result {
+        self.set_atoms(kill_clients, errors);
+        self.set_back_pixmap(clear)
+    }
+
+    /// Sets the pixmap as the back pixmap of the root window without touching
+    /// the root atoms and, if `clear` is true, clears the window so that the
+    /// new background is drawn.
+    fn set_back_pixmap(&self, clear: bool) -> Result {
         self.conn.send_and_check_request(&x::ChangeWindowAttributes {
             window: self.screen.root(),
             value_list: &[x::Cw::BackPixmap(self.pixmap)],
         })?;
-        self.conn.send_request(&x::ClearArea {
+        if !clear {
+            return Ok(());
+        }
+        self.send(&x::ClearArea {
             exposures: false,
             window: self.screen.root(),
             x: 0,
@@ -314,82 +1940,636 @@ impl<'a> RootPixmap<'a> {
         Ok(())
     }
 
+    /// Updates the root atoms without touching the root window’s back pixmap.
+    ///
+    /// Errors are appended to `errors`.  If `kill_clients` is true, kills
+    /// clients holding old root pixmaps and sets `RetainTemporary` close-down
+    /// mode.
+    fn set_atoms(&self, kill_clients: bool, errors: &mut Vec<Error>) {
+        self.set_root_atoms(kill_clients, errors);
+        if kill_clients {
+            self.send(&x::KillClient {
+                resource: 0, // AllTemporary
+            });
+            self.send(&x::SetCloseDownMode {
+                mode: x::CloseDown::RetainTemporary,
+            });
+        }
+    }
+
     /// Updates the atoms holding the root pixmap.
-    fn set_root_atoms(&self) {
-        let mut killed = x::Pixmap::none();
+    ///
+    /// Any errors are appended to `errors`.  Failure to update one of the atoms
+    /// doesn’t prevent attempting to update the other.  If `kill_clients` is
+    /// true, the client owning pixmap previously stored in the atoms is killed
+    /// (see [`stale_root_pixmap`]).
+    fn set_root_atoms(&self, kill_clients: bool, errors: &mut Vec<Error>) {
+        let mut old = Vec::with_capacity(2);
         for name in ["_XROOTPMAP_ID", "ESETROOT_PMAP_ID"] {
+            let atom_error = |err| Error::AtomUpdate(name.into(), err);
+
             // Intern the atom
             let mut intern_request =
                 x::InternAtom { only_if_exists: true, name: name.as_bytes() };
             let cookie = self.conn.send_request(&intern_request);
-            let atom = self.conn.wait_for_reply(cookie).and_then(|reply| {
-                let atom = reply.atom();
-                if atom.is_none() {
+            let atom = match self.conn.wait_for_reply(cookie) {
+                Ok(reply) if reply.atom().is_none() => {
                     // Atom doesn't exist, create it
                     intern_request.only_if_exists = false;
                     let cookie = self.conn.send_request(&intern_request);
                     self.conn.wait_for_reply(cookie).map(|reply| reply.atom())
-                } else {
-                    // Atom exists, clean up old pixmap
-                    self.clean_root_atom(atom, &mut killed);
+                }
+                Ok(reply) if kill_clients => {
+                    // Atom exists, remember old pixmap
+                    let atom = reply.atom();
+                    let root = self.screen.root();
+                    match get_pixmap_property(self.conn, root, atom) {
+                        Ok(pixmap) => old.push(pixmap),
+                        Err(err) => errors.push(atom_error(err)),
+                    }
                     Ok(atom)
                 }
-            });
-            // TODO(mpn): Report the errors.
+                Ok(reply) => Ok(reply.atom()),
+                Err(err) => Err(err),
+            };
             let atom = match atom {
-                Err(_err) => {
-                    //err!("x: InternAtom({}): {}", name, err);
-                    return;
+                Err(err) => {
+                    errors.push(atom_error(err));
+                    continue;
                 }
                 Ok(atom) if atom.is_none() => {
-                    //err!("x: failed to create {} atom", name);
-                    return;
+                    errors.push(Error::CouldNotInternAtom(name.into()));
+                    continue;
                 }
                 Ok(atom) => atom,
             };
 
             // Change Property
-            self.conn.send_request(&x::ChangeProperty {
+            let cookie = self.conn.send_request_checked(&x::ChangeProperty {
                 mode: x::PropMode::Replace,
                 window: self.screen.root(),
                 property: atom,
                 r#type: x::ATOM_PIXMAP,
                 data: &[self.pixmap.resource_id()],
             });
-            // TODO(mpn): Report the error.
-            // if let Err(err) = self.conn.check_request(cookie) {
-            //     err!("x: ChangeProperty({}): {}", name, err);
-            // }
+            if let Err(err) = self.conn.check_request(cookie) {
+                errors.push(atom_error(err.into()));
+            }
+        }
+
+        if let Some(pixmap) = stale_root_pixmap(&old, self.pixmap) &&
+            self.is_pixmap(pixmap)
+        {
+            let resource = pixmap.resource_id();
+            self.send(&x::KillClient { resource });
         }
     }
 
-    /// Cleans up old atoms holding the root pixmap.
-    fn clean_root_atom(&self, atom: Atom, prev_killed: &mut x::Pixmap) {
-        let cookie = self.conn.send_request(&x::GetProperty {
-            delete: false,
-            window: self.screen.root(),
-            property: atom,
-            r#type: x::ATOM_ANY,
-            long_offset: 0,
-            long_length: 1, // We only want 1 item (the pixmap ID)
+    /// Checks whether given id refers to an existing pixmap.
+    ///
+    /// Ids may be recycled once the pixmap is freed, so the id stored in the
+    /// atoms might now refer to a window or to no resource at all.
+    fn is_pixmap(&self, pixmap: x::Pixmap) -> bool {
+        let geometry = self.conn.send_request(&x::GetGeometry {
+            drawable: x::Drawable::Pixmap(pixmap),
         });
-        let reply = match self.conn.wait_for_reply(cookie) {
-            Ok(reply) => reply,
-            Err(_err) => {
-                // TODO(mpn): Report the error.
-                //err!("x: GetProperty({}): {}", name, err);
-                return;
-            }
+        let window = x::Window::new(pixmap.resource_id());
+        let attributes =
+            self.conn.send_request(&x::GetWindowAttributes { window });
+        // GetGeometry succeeds for any drawable; GetWindowAttributes fails for
+        // anything but a window.
+        let is_drawable = self.conn.wait_for_reply(geometry).is_ok();
+        let is_window = self.conn.wait_for_reply(attributes).is_ok();
+        is_drawable && !is_window
+    }
+}
+
+
+/// Returns pixmap whose owner should be killed when replacing the root
+/// pixmap.
+///
+/// `old` are pixmaps previously stored in the `_XROOTPMAP_ID` and
+/// `ESETROOT_PMAP_ID` atoms (`None` if an atom held no pixmap).  Following
+/// `Esetroot` convention, the pixmap is considered stale only if both atoms
+/// exist and refer to the same pixmap.  The new pixmap is never returned.
+/// This guarantees at most one client is killed and avoids killing a client
+/// based on a value only one program has written.
+#[cfg(feature = "std")]
+fn stale_root_pixmap(
+    old: &[Option<x::Pixmap>],
+    new: x::Pixmap,
+) -> Option<x::Pixmap> {
+    match *old {
+        [Some(a), Some(b)] if a == b && a != new => Some(a),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_stale_root_pixmap() {
+    let pixmap = |id| Some(x::Pixmap::new(id));
+    let new = x::Pixmap::new(1);
+
+    assert_eq!(pixmap(2), stale_root_pixmap(&[pixmap(2), pixmap(2)], new));
+    // The atoms disagree or one of them is missing.
+    assert_eq!(None, stale_root_pixmap(&[pixmap(2), pixmap(3)], new));
+    assert_eq!(None, stale_root_pixmap(&[pixmap(2), None], new));
+    assert_eq!(None, stale_root_pixmap(&[None, pixmap(2)], new));
+    assert_eq!(None, stale_root_pixmap(&[pixmap(2)], new));
+    assert_eq!(None, stale_root_pixmap(&[None, None], new));
+    assert_eq!(None, stale_root_pixmap(&[], new));
+    // Never kill the owner of the new pixmap.
+    assert_eq!(None, stale_root_pixmap(&[pixmap(1), pixmap(1)], new));
+}
+
+
+/// Reads a property holding a pixmap id from given window.
+///
+/// Returns `None` if the property isn’t set, is of a wrong type or holds
+/// a zero id.
+#[cfg(feature = "std")]
+fn get_pixmap_property(
+    conn: &xcb::Connection,
+    window: x::Window,
+    property: Atom,
+) -> xcb::Result<Option<x::Pixmap>> {
+    let cookie = conn.send_request(&x::GetProperty {
+        delete: false,
+        window,
+        property,
+        r#type: x::ATOM_ANY,
+        long_offset: 0,
+        long_length: 1, // We only want 1 item (the pixmap ID)
+    });
+    let reply = conn.wait_for_reply(cookie)?;
+    if reply.r#type() == x::ATOM_PIXMAP &&
+        reply.format() == 32 &&
+        let &[resource] = reply.value::<u32>() &&
+        resource != 0
+    {
+        Ok(Some(x::Pixmap::new(resource)))
+    } else {
+        Ok(None)
+    }
+}
+
+
+/// Returns smallest rectangle containing all given rectangles or `None` if
+/// there are no rectangles.  Rectangles are `(x, y, width, height)` tuples.
+#[cfg(feature = "std")]
+fn bounding_box(
+    rects: impl Iterator<Item = (i16, i16, u16, u16)>,
+) -> Option<(i16, i16, u16, u16)> {
+    let (left, top, right, bottom) = rects
+        .map(|(x, y, w, h)| {
+            let (x, y) = (i32::from(x), i32::from(y));
+            (x, y, x + i32::from(w), y + i32::from(h))
+        })
+        .reduce(|a, b| {
+            (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+        })?;
+    let len = |len: i32| len.clamp(0, i32::from(u16::MAX)) as u16;
+    Some((left as i16, top as i16, len(right - left), len(bottom - top)))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_bounding_box() {
+    assert_eq!(None, bounding_box([].into_iter()));
+    assert_eq!(
+        Some((0, 0, 1920, 1080)),
+        bounding_box([(0, 0, 1920, 1080)].into_iter())
+    );
+    assert_eq!(
+        Some((-1280, -200, 3200, 1280)),
+        bounding_box(
+            [(0, 0, 1920, 1080), (-1280, -200, 1280, 1024)].into_iter()
+        )
+    );
+    assert_eq!(
+        Some((0, 0, 3840, 1080)),
+        bounding_box([(0, 0, 1920, 1080), (1920, 0, 1920, 1080)].into_iter())
+    );
+}
+
+/// Verifies that given rectangle fits inside of an area of given dimensions.
+#[cfg(feature = "std")]
+fn check_rect(
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    area: (u16, u16),
+) -> Result<x::Rectangle> {
+    let fits = |pos: i16, len: u16, limit: u16| {
+        u16::try_from(pos).is_ok_and(|pos| {
+            u32::from(pos) + u32::from(len) <= u32::from(limit)
+        })
+    };
+    if fits(x, width, area.0) && fits(y, height, area.1) {
+        Ok(x::Rectangle { x, y, width, height })
+    } else {
+        Err(Error::RectOutOfBounds(x, y, width, height))
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_check_rect() {
+    let check = |x, y, width, height| {
+        check_rect(x, y, width, height, (1920, 1080))
+            .map(|rect| (rect.x, rect.y, rect.width, rect.height))
+            .map_err(|err| err.to_string())
+    };
+
+    assert_eq!(Ok((0, 0, 1920, 1080)), check(0, 0, 1920, 1080));
+    assert_eq!(Ok((10, 20, 100, 50)), check(10, 20, 100, 50));
+    assert_eq!(Ok((1919, 1079, 1, 1)), check(1919, 1079, 1, 1));
+    assert_eq!(Ok((1920, 1080, 0, 0)), check(1920, 1080, 0, 0));
+
+    let err = "rectangle 100x50-1+0 does not fit in the pixmap";
+    assert_eq!(Err(err.into()), check(-1, 0, 100, 50));
+    assert!(check(0, -1, 100, 50).is_err());
+    assert!(check(1900, 0, 21, 50).is_err());
+    assert!(check(0, 1000, 100, 81).is_err());
+    assert!(check(0, 0, u16::MAX, 1).is_err());
+}
+
+/// Encodes text as a string for `ImageText8` request.
+///
+/// Non-ASCII characters are replaced by question marks and the text is
+/// truncated to 255 characters, the most a single request can draw.
+#[cfg(feature = "std")]
+fn text_bytes(text: &str) -> Vec<u8> {
+    text.chars()
+        .take(255)
+        .map(|ch| if ch.is_ascii() { ch as u8 } else { b'?' })
+        .collect()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_text_bytes() {
+    assert_eq!(b"hello", text_bytes("hello").as_slice());
+    assert_eq!(b"Z?rich", text_bytes("Zürich").as_slice());
+    assert_eq!(Vec::<u8>::new(), text_bytes(""));
+    assert_eq!(255, text_bytes(&"x".repeat(300)).len());
+}
+
+/// Splits an image into bands of scanlines which fit in a single `PutImage`
+/// request.
+///
+/// `stride` is length of a single scanline in bytes and `max_len` is the
+/// maximum request length in bytes.  Returns iterator over `(row, rows)` pairs
+/// describing index of the first row and number of rows in each band.  If
+/// even a single scanline doesn’t fit in a request, returns
+/// [`Error::RequestTooLarge`] error.
+#[cfg(feature = "std")]
+fn split_rows(
+    stride: usize,
+    height: u16,
+    max_len: usize,
+) -> Result<impl Iterator<Item = (u16, u16)>> {
+    // Size of PutImage request header assuming BIG-REQUESTS encoding.
+    const HEADER_LEN: usize = 28;
+    if height != 0 && HEADER_LEN + stride > max_len {
+        let bytes = HEADER_LEN + stride;
+        return Err(Error::RequestTooLarge { bytes, max: max_len });
+    }
+    let rows = max_len.saturating_sub(HEADER_LEN) / stride.max(1);
+    let rows = rows.clamp(1, usize::from(u16::MAX)) as u16;
+    Ok((0..height)
+        .step_by(usize::from(rows))
+        .map(move |row| (row, rows.min(height - row))))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_split_rows() {
+    let split = |stride, height, max_len| {
+        split_rows(stride, height, max_len).unwrap().collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec![(0, 1080)], split(7680, 1080, 16 << 20));
+    assert_eq!(vec![(0, 3), (3, 3), (6, 1)], split(40, 7, 28 + 120));
+    assert_eq!(vec![(0, 3), (3, 3), (6, 1)], split(40, 7, 28 + 159));
+    assert_eq!(vec![(0, 1), (1, 1)], split(40, 2, 28 + 40));
+    assert!(matches!(
+        split_rows(40, 2, 28 + 39).map(|it| it.count()),
+        Err(Error::RequestTooLarge { bytes: 68, max: 67 })
+    ));
+    assert_eq!(Vec::<(u16, u16)>::new(), split(40, 0, 1000));
+
+    // Verify that bands put together reconstruct the whole image.
+    let (stride, height) = (12, 10u16);
+    let image = (0..stride * usize::from(height)).collect::<Vec<_>>();
+    let mut rebuilt = Vec::new();
+    for (row, rows) in split_rows(stride, height, 28 + 3 * stride).unwrap() {
+        assert!(rows <= 3);
+        let start = usize::from(row) * stride;
+        rebuilt
+            .extend_from_slice(&image[start..][..usize::from(rows) * stride]);
+    }
+    assert_eq!(image, rebuilt);
+}
+
+/// Chooses visual whose masks define pixel format of the root depth.
+///
+/// Normally that’s the root visual.  Some servers however report root visual
+/// id which isn’t listed among visuals of the root depth.  In that case, falls
+/// back to the first usable TrueColour visual or, failing that, the first
+/// usable DirectColour visual of the depth.  Returns
+/// [`Error::CouldNotFindRootVisual`] if there’s no such visual.
+#[cfg(feature = "std")]
+fn choose_visual<'v>(
+    visuals: impl Iterator<Item = &'v x::Visualtype> + Clone,
+    depth: u8,
+    root_visual: x::Visualid,
+) -> Result<(x::Visualid, img::RgbShifts)> {
+    let with_shifts = |vis: &x::Visualtype| {
+        visual_rgb_shifts(vis, depth).map(|shifts| (vis.visual_id(), shifts))
+    };
+    if let Some(vis) =
+        visuals.clone().find(|vis| vis.visual_id() == root_visual)
+    {
+        return with_shifts(vis);
+    }
+    let usable = |class| {
+        visuals
+            .clone()
+            .filter(move |vis| vis.class() == class)
+            .find_map(|vis| with_shifts(vis).ok())
+    };
+    usable(x::VisualClass::TrueColor)
+        .or_else(|| usable(x::VisualClass::DirectColor))
+        .ok_or(Error::CouldNotFindRootVisual(root_visual))
+}
+
+/// Returns RGB shifts describing pixel format of given visual of given depth.
+///
+/// Supports `TrueColor` and `DirectColor` visuals.  The latter have the same
+/// pixel layout but a writable colormap.  The shifts assume the colormap maps
+/// each value to itself which is the case for the default colormap but not
+/// necessarily if a client has changed it (e.g. to apply gamma correction).
+#[cfg(feature = "std")]
+fn visual_rgb_shifts(vis: &x::Visualtype, depth: u8) -> Result<img::RgbShifts> {
+    use x::VisualClass::{DirectColor, TrueColor};
+    let class = vis.class();
+    if matches!(class, TrueColor | DirectColor) &&
+        matches!(depth, 16 | 24 | 30 | 32)
+    {
+        let shifts = img::RgbShifts::from_masks(
+            vis.red_mask(),
+            vis.green_mask(),
+            vis.blue_mask(),
+        );
+        // On 32-bit visuals, the remaining byte holds alpha.
+        let shifts = if depth == 32 {
+            shifts.map(|shifts| shifts.with_alpha().unwrap_or(shifts))
+        } else {
+            shifts
         };
+        if let Some(shifts) = shifts {
+            return Ok(shifts);
+        }
+    }
+    Err(Error::UnsupportedVisual(depth, class))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_visual_rgb_shifts() {
+    let visual = |class| {
+        x::Visualtype::new(42, class, 8, 256, 0xFF0000, 0x00FF00, 0x0000FF)
+    };
+    let shifts = |class, depth| visual_rgb_shifts(&visual(class), depth).ok();
+
+    let want = Some(img::RgbShifts::XRGB8888);
+    assert_eq!(want, shifts(x::VisualClass::TrueColor, 24));
+    assert_eq!(want, shifts(x::VisualClass::DirectColor, 24));
+    let want = Some(img::RgbShifts::ARGB8888);
+    assert_eq!(want, shifts(x::VisualClass::DirectColor, 32));
+    assert_eq!(None, shifts(x::VisualClass::PseudoColor, 24));
+    assert_eq!(None, shifts(x::VisualClass::TrueColor, 8));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_choose_visual() {
+    use x::VisualClass::{DirectColor, PseudoColor, TrueColor};
+
+    let visual = |id, class, red| {
+        x::Visualtype::new(id, class, 8, 256, red, 0x00FF00, 0x0000FF)
+    };
+    let visuals = [
+        visual(1, PseudoColor, 0xFF0000),
+        visual(2, DirectColor, 0xFF0000),
+        visual(3, TrueColor, 0xF00000),
+        visual(4, TrueColor, 0xFF0000),
+    ];
+    let choose = |visuals: &[x::Visualtype], root| {
+        choose_visual(visuals.iter(), 24, root).map(|(id, _)| id).ok()
+    };
+
+    // Root visual is used if present, even if a TrueColour visual exists.
+    assert_eq!(Some(2), choose(&visuals, 2));
+    // Root visual is unsupported and there’s no fallback.
+    assert_eq!(None, choose(&visuals, 1));
+    // Root visual missing; the first usable TrueColour visual is picked.
+    assert_eq!(Some(4), choose(&visuals, 42));
+    // With no TrueColour visuals, DirectColour is picked.
+    assert_eq!(Some(2), choose(&visuals[..3], 42));
+    assert_eq!(None, choose(&visuals[..1], 42));
+}
+
+/// Layout of images in `ZPixmap` format of the root depth as expected by the
+/// X display server.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PixelFormat {
+    /// Number of bits per pixel.  Either 16 or 32.
+    bits_per_pixel: u8,
+    /// Number of bits each scanline is padded to.  A multiple of eight.
+    scanline_pad: u8,
+    /// Whether server’s image byte order differs from host’s.
+    swap_bytes: bool,
+}
+
+#[cfg(feature = "std")]
+impl PixelFormat {
+    /// Reads the format of images of screen’s root depth from connection
+    /// setup.
+    ///
+    /// Only 16- and 32-bit pixels are supported.  In practice, X servers use
+    /// the former for depth 16 and the latter for depths 24, 30 and 32.
+    fn get(conn: &xcb::Connection, scr: &x::Screen) -> Result<Self> {
+        let setup = conn.get_setup();
+        let root_depth = scr.root_depth();
+        // Pixels are sent in host’s byte order which may differ from the
+        // server’s if the connection is over the network.
+        let swap_bytes = setup.image_byte_order() !=
+            if cfg!(target_endian = "little") {
+                x::ImageOrder::LsbFirst
+            } else {
+                x::ImageOrder::MsbFirst
+            };
+        setup
+            .pixmap_formats()
+            .iter()
+            .find(|format| format.depth() == root_depth)
+            .map(|format| Self {
+                bits_per_pixel: format.bits_per_pixel(),
+                scanline_pad: format.scanline_pad(),
+                swap_bytes,
+            })
+            .filter(|format| {
+                matches!(format.bits_per_pixel, 16 | 32) &&
+                    format.scanline_pad != 0 &&
+                    format.scanline_pad % 8 == 0
+            })
+            .ok_or(Error::UnsupportedVisual(
+                root_depth,
+                x::VisualClass::TrueColor,
+            ))
+    }
+
+    /// Returns length in bytes of a padded scanline of given width.
+    fn stride(&self, width: u16) -> usize {
+        let bits = usize::from(width) * usize::from(self.bits_per_pixel);
+        let pad = usize::from(self.scanline_pad);
+        bits.div_ceil(pad) * pad / 8
+    }
 
-        if reply.r#type() == x::ATOM_PIXMAP &&
-            reply.format() == 32 &&
-            let &[resource] = reply.value::<u32>() &&
-            resource != 0 &&
-            resource != prev_killed.resource_id()
+    /// Converts buffer of `u32` pixels of an image with given width into the
+    /// format.
+    ///
+    /// With 16 bits per pixel, truncates each pixel to its two least
+    /// significant bytes which is where [`img::RgbShifts::RGB565`] puts the
+    /// colour.  If necessary, reverses order of bytes in each pixel and pads
+    /// the scanlines.  With 32 bits per pixel, returns the buffer unchanged
+    /// if neither is necessary.
+    fn pack<'b>(&self, data: &'b [u8], width: u16) -> Cow<'b, [u8]> {
+        let stride = self.stride(width);
+        if width == 0 ||
+            (self.bits_per_pixel == 32 &&
+                !self.swap_bytes &&
+                stride == usize::from(width) * 4)
         {
-            self.conn.send_request(&x::KillClient { resource });
-            *prev_killed = x::Pixmap::new(resource);
+            return Cow::Borrowed(data);
+        }
+        let (pixels, _) = data.as_chunks::<4>();
+        let rows = pixels.chunks(usize::from(width));
+        let mut out = Vec::with_capacity(rows.len() * stride);
+        for row in rows {
+            let start = out.len();
+            for &px in row {
+                let px = u32::from_ne_bytes(px);
+                if self.bits_per_pixel == 32 {
+                    let px = if self.swap_bytes { px.swap_bytes() } else { px };
+                    out.extend_from_slice(&px.to_ne_bytes());
+                } else {
+                    let px = px as u16;
+                    let px = if self.swap_bytes { px.swap_bytes() } else { px };
+                    out.extend_from_slice(&px.to_ne_bytes());
+                }
+            }
+            out.resize(start + stride, 0);
+        }
+        Cow::Owned(out)
+    }
+
+    /// Converts buffer of an image with given width in the format into `u32`
+    /// pixels.  This is an inverse of [`Self::pack`].
+    fn unpack<'b>(
+        &self,
+        data: &'b [u8],
+        width: u16,
+    ) -> impl Iterator<Item = u32> + 'b {
+        let Self { bits_per_pixel, swap_bytes, .. } = *self;
+        let bytes_per_pixel = usize::from(bits_per_pixel / 8);
+        let row_len = usize::from(width) * bytes_per_pixel;
+        data.chunks(self.stride(width).max(1))
+            .flat_map(move |row| row[..row_len].chunks_exact(bytes_per_pixel))
+            .map(move |px| {
+                let px = match *px {
+                    [a, b] => u32::from(u16::from_ne_bytes([a, b])),
+                    [a, b, c, d] => u32::from_ne_bytes([a, b, c, d]),
+                    _ => unreachable!(),
+                };
+                match (swap_bytes, bits_per_pixel) {
+                    (false, _) => px,
+                    (true, 32) => px.swap_bytes(),
+                    (true, _) => u32::from((px as u16).swap_bytes()),
+                }
+            })
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_pixel_format() {
+    let format =
+        PixelFormat { bits_per_pixel: 32, scanline_pad: 32, swap_bytes: false };
+    let pixels = [0x0000_F81F_u32, 0x0000_07E0];
+    let data: &[u8] = bytemuck::cast_slice(&pixels);
+    assert!(matches!(format.pack(data, 2), Cow::Borrowed(_)));
+    assert_eq!(pixels.to_vec(), format.unpack(data, 2).collect::<Vec<_>>());
+
+    let format = PixelFormat { bits_per_pixel: 16, ..format };
+    let packed = format.pack(data, 2);
+    let want: &[u8] = bytemuck::cast_slice(&[0xF81F_u16, 0x07E0]);
+    assert_eq!(want, &*packed);
+    assert_eq!(pixels.to_vec(), format.unpack(&packed, 2).collect::<Vec<_>>());
+
+    let format = PixelFormat { swap_bytes: true, ..format };
+    let packed = format.pack(data, 2);
+    let want: &[u8] = bytemuck::cast_slice(&[0x1FF8_u16, 0xE007]);
+    assert_eq!(want, &*packed);
+    assert_eq!(pixels.to_vec(), format.unpack(&packed, 2).collect::<Vec<_>>());
+
+    let format = PixelFormat { bits_per_pixel: 32, ..format };
+    let packed = format.pack(data, 2);
+    let want: &[u8] = bytemuck::cast_slice(&[0x1FF8_0000_u32, 0xE007_0000]);
+    assert_eq!(want, &*packed);
+    assert_eq!(pixels.to_vec(), format.unpack(&packed, 2).collect::<Vec<_>>());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_pixel_format_padding() {
+    let format =
+        PixelFormat { bits_per_pixel: 16, scanline_pad: 32, swap_bytes: false };
+    assert_eq!(0, format.stride(0));
+    assert_eq!(4, format.stride(1));
+    assert_eq!(4, format.stride(2));
+    assert_eq!(8, format.stride(3));
+
+    // Two rows of three 16-bit pixels padded to 64 bits each.
+    let format = PixelFormat { scanline_pad: 64, ..format };
+    let pixels = [1_u32, 2, 3, 4, 5, 6];
+    let packed = format.pack(bytemuck::cast_slice(&pixels), 3);
+    let want: &[u8] = bytemuck::cast_slice(&[1_u16, 2, 3, 0, 4, 5, 6, 0]);
+    assert_eq!(want, &*packed);
+    assert_eq!(pixels.to_vec(), format.unpack(&packed, 3).collect::<Vec<_>>());
+
+    // 32-bit pixels require padding if the scanline pad is greater.
+    let format = PixelFormat { bits_per_pixel: 32, ..format };
+    let packed = format.pack(bytemuck::cast_slice(&pixels), 3);
+    let want: &[u8] = bytemuck::cast_slice(&[1_u32, 2, 3, 0, 4, 5, 6, 0]);
+    assert_eq!(want, &*packed);
+    assert_eq!(pixels.to_vec(), format.unpack(&packed, 3).collect::<Vec<_>>());
+}
+
+/// Interprets buffer returned by [`img::IntoXBuffer::into_x_buffer`] as slice
+/// of pixels.  Copies the data only if the buffer isn’t correctly aligned.
+#[cfg(feature = "std")]
+fn to_pixels(buffer: &[u8]) -> Cow<'_, [u32]> {
+    match bytemuck::try_cast_slice(buffer) {
+        Ok(pixels) => Cow::Borrowed(pixels),
+        Err(_) => {
+            let (chunks, _) = buffer.as_chunks();
+            chunks.iter().copied().map(u32::from_ne_bytes).collect()
         }
     }
 }
@@ -0,0 +1,88 @@
+//! Support for MIT-SHM extension which allows transferring images to the X
+//! display server via shared memory rather than over the socket.
+
+use xcb::shm;
+
+/// A System V shared memory segment attached to the X display server.
+///
+/// The segment is detached from the server and the client when the object is
+/// dropped.  It’s marked for removal as soon as it’s attached by the server so
+/// the memory is freed once both sides detach.
+pub(crate) struct Segment<'a> {
+    conn: &'a xcb::Connection,
+    seg: shm::Seg,
+    addr: *mut u8,
+    len: usize,
+}
+
+impl<'a> Segment<'a> {
+    /// Allocates a new `len`-byte segment and attaches it to the server.
+    ///
+    /// Returns `None` if the MIT-SHM extension isn’t available or the segment
+    /// couldn’t be created or attached (e.g. because the server is on
+    /// a remote machine).
+    pub fn new(conn: &'a xcb::Connection, len: usize) -> Option<Self> {
+        if len == 0 || !Self::is_supported(conn) {
+            return None;
+        }
+
+        // SAFETY: Calling C functions with valid arguments.  Return values are
+        // checked for errors.
+        let shmid = unsafe {
+            libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600)
+        };
+        if shmid < 0 {
+            return None;
+        }
+        let addr = unsafe { libc::shmat(shmid, core::ptr::null(), 0) };
+        let remove = || unsafe {
+            libc::shmctl(shmid, libc::IPC_RMID, core::ptr::null_mut())
+        };
+        if addr as isize == -1 {
+            remove();
+            return None;
+        }
+
+        let seg = conn.generate_id::<shm::Seg>();
+        let res = conn.send_and_check_request(&shm::Attach {
+            shmseg: seg,
+            shmid: shmid as u32,
+            read_only: true,
+        });
+        // Whether the server managed to attach or not, the segment can be
+        // marked for removal now.  It’ll be destroyed once we detach.
+        remove();
+        if res.is_err() {
+            unsafe { libc::shmdt(addr) };
+            return None;
+        }
+
+        Some(Self { conn, seg, addr: addr.cast(), len })
+    }
+
+    /// Checks whether the X display server supports MIT-SHM extension.
+    ///
+    /// XCB caches extension data in the connection so only the first call
+    /// on a given connection round-trips to the server.
+    fn is_supported(conn: &xcb::Connection) -> bool {
+        shm::get_extension_data(conn).is_some()
+    }
+
+    /// Returns the segment id as known by the X display server.
+    pub fn seg(&self) -> shm::Seg { self.seg }
+
+    /// Returns the shared memory as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: addr points at len-byte segment attached by us.  The server
+        // has it attached read-only.
+        unsafe { core::slice::from_raw_parts_mut(self.addr, self.len) }
+    }
+}
+
+impl core::ops::Drop for Segment<'_> {
+    fn drop(&mut self) {
+        self.conn.send_request(&shm::Detach { shmseg: self.seg });
+        // SAFETY: addr was returned by successful shmat call.
+        unsafe { libc::shmdt(self.addr.cast()) };
+    }
+}
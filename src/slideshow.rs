@@ -0,0 +1,97 @@
+//! Wallpaper rotator cycling through images.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{Display, Result, img};
+
+/// Cycles desktop background through a list of images.
+///
+/// Each call to [`Self::tick`] loads the next image, scales it to cover the
+/// screen according to configured mode and sets it as the background.  Timing
+/// is left to the caller such that the slideshow can be driven from any event
+/// loop; [`Self::run`] is a convenience blocking loop which sleeps between the
+/// images.
+///
+/// A scratch conversion buffer is reused across images to avoid reallocating
+/// a screen-sized buffer each time.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let display = setroot::Display::open().unwrap();
+/// let paths = vec!["a.jpg".into(), "b.jpg".into()];
+/// let interval = Duration::from_secs(15 * 60);
+/// let mode = setroot::img::ScaleMode::Fill;
+/// let slideshow = setroot::Slideshow::new(display, paths, interval, mode);
+/// if let Err(err) = slideshow.run() {
+///     eprintln!("slideshow: {err}");
+/// }
+/// ```
+pub struct Slideshow {
+    display: Display,
+    paths: Vec<PathBuf>,
+    interval: Duration,
+    mode: img::ScaleMode,
+    next: usize,
+    scratch: Vec<u32>,
+}
+
+impl Slideshow {
+    /// Constructs a new slideshow cycling through given images.
+    ///
+    /// Images are loaded with [`image::open`] when they are about to be
+    /// displayed so errors in decoding them are reported by [`Self::tick`].
+    /// Note that the crate doesn’t enable any image formats in the `image`
+    /// crate.  Formats which should be supported need to be enabled by the
+    /// application.
+    pub fn new(
+        display: Display,
+        paths: Vec<PathBuf>,
+        interval: Duration,
+        mode: img::ScaleMode,
+    ) -> Self {
+        Self { display, paths, interval, mode, next: 0, scratch: Vec::new() }
+    }
+
+    /// Returns the display the slideshow sets background of.
+    pub fn display(&self) -> &Display { &self.display }
+
+    /// Returns interval between images.
+    pub fn interval(&self) -> Duration { self.interval }
+
+    /// Advances the slideshow to the next image and sets it as the desktop
+    /// background.
+    ///
+    /// After the last image, starts over with the first one.  Does nothing if
+    /// the list of images is empty.  If loading or drawing an image fails, the
+    /// error is returned and the next call proceeds to the following image
+    /// such that a single broken file doesn’t stall the slideshow.
+    pub fn tick(&mut self) -> Result {
+        let Some(path) = self.paths.get(self.next) else {
+            return Ok(());
+        };
+        self.next = (self.next + 1) % self.paths.len();
+
+        let image = image::open(path)?.into_rgba8();
+        let wallpaper = self.display.wallpaper().mode(self.mode);
+        wallpaper.set_reusing(&image, &mut self.scratch)
+    }
+
+    /// Runs the slideshow until an error occurs.
+    ///
+    /// Calls [`Self::tick`] and sleeps for the configured interval in a loop.
+    /// Returns `Ok(())` immediately if the list of images is empty; otherwise
+    /// only returns on error.
+    pub fn run(mut self) -> Result {
+        if self.paths.is_empty() {
+            return Ok(());
+        }
+        loop {
+            self.tick()?;
+            std::thread::sleep(self.interval);
+        }
+    }
+}
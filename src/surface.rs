@@ -0,0 +1,1168 @@
+//! Drawing logic shared by surfaces images can be rendered onto.
+
+use xcb::x;
+
+use crate::{Error, Rect, Result, check_rect, img, to_pixels};
+
+/// A surface images are drawn onto.
+///
+/// Implementations provide only a handful of primitive operations.  Image
+/// conversion, scaling, tiling and gradients are implemented on top of them by
+/// provided methods.  This way [`crate::RootPixmap`] shares all that logic with
+/// surfaces which don’t need an X display server, which makes it possible to
+/// test it without one.
+pub(crate) trait Surface {
+    /// Returns dimensions of the surface.
+    fn dimensions(&self) -> (u16, u16);
+
+    /// Returns RGB shifts which define pixel format used by the surface.
+    fn rgb_shifts(&self) -> img::RgbShifts;
+
+    /// Puts a `width`×`height` image in the surface’s pixel format at given
+    /// location.
+    ///
+    /// `data` holds exactly `width * height` pixels.  Parts of the image which
+    /// fall outside of the surface are clipped.
+    fn put_raw_impl(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> Result;
+
+    /// Fills given rectangles with a solid colour.
+    ///
+    /// The rectangles lie inside of the surface.
+    fn fill_rects_impl(&self, rects: &[x::Rectangle], colour: u32) -> Result;
+
+    /// Reads pixels in given rectangle in the surface’s pixel format.
+    ///
+    /// The rectangle lies inside of the surface.
+    fn get_raw_impl(&self, rect: x::Rectangle) -> Result<Vec<u32>>;
+
+    /// Converts image into the surface’s pixel format.
+    ///
+    /// Verifies that the returned buffer matches the image dimensions.
+    fn convert<'b, I: img::IntoXBuffer<'b>>(
+        &self,
+        img: I,
+    ) -> Result<((u16, u16), I::Buffer)> {
+        convert_to(img, self.rgb_shifts())
+    }
+
+    /// Converts image into the surface’s pixel format storing the pixels in
+    /// given buffer.
+    ///
+    /// Returns dimensions of the image after verifying that they match the
+    /// number of pixels.
+    fn convert_into<'b>(
+        &self,
+        img: impl img::IntoXBuffer<'b>,
+        out: &mut Vec<u32>,
+    ) -> Result<(u16, u16)> {
+        let dim = img.dimensions()?;
+        img.into_x_buffer_in(self.rgb_shifts(), out)?;
+        if usize::from(dim.0) * usize::from(dim.1) != out.len() {
+            return Err(Error::BadBufferSize(out.len() * 4, dim.0, dim.1));
+        }
+        Ok(dim)
+    }
+
+    /// See [`crate::RootPixmap::put_image`].
+    fn put_image<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let ((width, height), buffer) = self.convert(img)?;
+        self.put_raw_impl(dst_x, dst_y, width, height, buffer.as_ref())
+    }
+
+    /// See [`crate::RootPixmap::put_image_rotated`].
+    fn put_image_rotated<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        rotation: img::Rotation,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        if rotation == img::Rotation::None {
+            return self.put_image(dst_x, dst_y, img);
+        }
+        let ((width, height), data) = self.convert_rotated(rotation, img)?;
+        self.put_raw(dst_x, dst_y, width, height, &data)
+    }
+
+    /// Converts image into the surface’s pixel format and rotates it.
+    ///
+    /// Returns dimensions and pixels of the rotated image.  The image is
+    /// converted into a vector which is then rotated in place.
+    fn convert_rotated<'b>(
+        &self,
+        rotation: img::Rotation,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<((u16, u16), Vec<u32>)> {
+        let mut data = Vec::new();
+        let dim = self.convert_into(img, &mut data)?;
+        img::rotate_in_place(&mut data, dim, rotation);
+        Ok((rotation.rotate_dimensions(dim), data))
+    }
+
+    /// See [`crate::RootPixmap::put_crossfade`].
+    fn put_crossfade<'b, 'c>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        a: impl img::IntoXBuffer<'b>,
+        b: impl img::IntoXBuffer<'c>,
+        t: f32,
+    ) -> Result {
+        let ((width, height), a) = self.convert(a)?;
+        let (dim, b) = self.convert(b)?;
+        if dim != (width, height) {
+            return Err(Error::BadBufferSize(b.as_ref().len(), width, height));
+        }
+        let (a, b) = (to_pixels(a.as_ref()), to_pixels(b.as_ref()));
+        let data = img::crossfade(&a, &b, t, self.rgb_shifts());
+        self.put_raw(dst_x, dst_y, width, height, &data)
+    }
+
+    /// Blends an image over current contents of the surface at given location.
+    ///
+    /// Works like [`Self::put_crossfade`] with current contents of the surface
+    /// used as the first image.  The image must lie inside of the surface.
+    fn put_blended<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+        t: f32,
+    ) -> Result {
+        let ((width, height), buffer) = self.convert(img)?;
+        let rect = check_rect(dst_x, dst_y, width, height, self.dimensions())?;
+        let current = self.get_raw_impl(rect)?;
+        let data = img::crossfade(
+            &current,
+            &to_pixels(buffer.as_ref()),
+            t,
+            self.rgb_shifts(),
+        );
+        self.put_raw(dst_x, dst_y, width, height, &data)
+    }
+
+    /// See [`crate::RootPixmap::composite`].
+    fn composite<'b, I: img::IntoXBuffer<'b> + Clone>(
+        &self,
+        layers: &[img::Layer<I>],
+    ) -> Result {
+        let (surface_w, surface_h) = self.dimensions();
+        let clip = |pos: i16, len: u16, limit: u16| {
+            let start = i32::from(pos).max(0);
+            let end = (i32::from(pos) + i32::from(len)).min(i32::from(limit));
+            (start < end).then_some((start, end))
+        };
+
+        // Find visible layers and the bounding box of the area they cover.
+        let mut visible = Vec::with_capacity(layers.len());
+        let mut bbox: Option<(i32, i32, i32, i32)> = None;
+        for layer in layers {
+            // Skip transparent layers.  Handle NaNs.
+            #[allow(clippy::neg_cmp_op_on_partial_ord)]
+            if !(layer.opacity > 0.0) {
+                continue;
+            }
+            let (width, height) = layer.img.dimensions()?;
+            let Some((x0, x1)) = clip(layer.x, width, surface_w) else {
+                continue;
+            };
+            let Some((y0, y1)) = clip(layer.y, height, surface_h) else {
+                continue;
+            };
+            bbox = Some(bbox.map_or((x0, y0, x1, y1), |(a, b, c, d)| {
+                (a.min(x0), b.min(y0), c.max(x1), d.max(y1))
+            }));
+            visible.push(layer);
+        }
+        let Some((bx0, by0, bx1, by1)) = bbox else {
+            return Ok(());
+        };
+
+        let rect = x::Rectangle {
+            x: bx0 as i16,
+            y: by0 as i16,
+            width: (bx1 - bx0) as u16,
+            height: (by1 - by0) as u16,
+        };
+        let stride = usize::from(rect.width);
+        let shifts = self.rgb_shifts();
+        let mut canvas = self
+            .get_raw_impl(rect)?
+            .into_iter()
+            .map(|px| shifts.to_rgb(px))
+            .collect::<Vec<_>>();
+
+        for layer in visible {
+            let ((width, height), buffer) =
+                convert_to(layer.img.clone(), img::RgbShifts::ARGB8888)?;
+            let pixels = to_pixels(buffer.as_ref());
+            let opacity = (layer.opacity.min(1.0) * 255.0).round() as u32;
+            let lines = pixels.chunks_exact(usize::from(width).max(1));
+            for (row, line) in lines.take(usize::from(height)).enumerate() {
+                let y = i32::from(layer.y) + row as i32 - by0;
+                if !(0..i32::from(rect.height)).contains(&y) {
+                    continue;
+                }
+                for (col, &px) in line.iter().enumerate() {
+                    let x = i32::from(layer.x) + col as i32 - bx0;
+                    if !(0..i32::from(rect.width)).contains(&x) {
+                        continue;
+                    }
+                    let alpha = ((px >> 24) * opacity + 127) / 255;
+                    if alpha == 0 {
+                        continue;
+                    }
+                    let (r, g, b) = img::RgbShifts::ARGB8888.to_rgb(px);
+                    let dst = &mut canvas[y as usize * stride + x as usize];
+                    let alpha = alpha as u8;
+                    *dst = (
+                        img::blend(r, dst.0, alpha),
+                        img::blend(g, dst.1, alpha),
+                        img::blend(b, dst.2, alpha),
+                    );
+                }
+            }
+        }
+
+        let data = canvas
+            .into_iter()
+            .map(|(r, g, b)| shifts.from_rgb(r, g, b))
+            .collect::<Vec<_>>();
+        self.put_raw(rect.x, rect.y, rect.width, rect.height, &data)
+    }
+
+    /// See [`crate::RootPixmap::put_image_reusing`].
+    fn put_image_reusing<'b>(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+        scratch: &mut Vec<u32>,
+    ) -> Result {
+        let (width, height) = img.dimensions()?;
+        img.into_x_buffer_in(self.rgb_shifts(), scratch)?;
+        self.put_raw(dst_x, dst_y, width, height, scratch)
+    }
+
+    /// See [`crate::RootPixmap::put_image_scaled`].
+    fn put_image_scaled<'b>(
+        &self,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        let gravity = img::Gravity::Center;
+        self.put_image_scaled_with_gravity(mode, gravity, filter, img)
+    }
+
+    /// See [`crate::RootPixmap::put_image_scaled_with_gravity`].
+    fn put_image_scaled_with_gravity<'b>(
+        &self,
+        mode: img::ScaleMode,
+        gravity: img::Gravity,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        let (dim, buffer) = self.convert(img)?;
+        let pixels = to_pixels(buffer.as_ref());
+        let (width, height) = self.dimensions();
+        let area = (0, 0, width, height);
+        self.put_pixels_in(&pixels, dim, area, mode, gravity, filter)
+    }
+
+    /// See [`crate::RootPixmap::put_image_on_monitor`].
+    fn put_image_in<'b>(
+        &self,
+        area: (i16, i16, u16, u16),
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        let (dim, buffer) = self.convert(img)?;
+        let pixels = to_pixels(buffer.as_ref());
+        let gravity = img::Gravity::Center;
+        self.put_pixels_in(&pixels, dim, area, mode, gravity, filter)
+    }
+
+    /// Works like [`Self::put_image_in`] but rotates the image before scaling
+    /// it.  See [`crate::RootPixmap::put_image_on_monitor`].
+    fn put_image_rotated_in<'b>(
+        &self,
+        area: (i16, i16, u16, u16),
+        rotation: img::Rotation,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result<Rect> {
+        if rotation == img::Rotation::None {
+            return self.put_image_in(area, mode, filter, img);
+        }
+        let (dim, data) = self.convert_rotated(rotation, img)?;
+        let gravity = img::Gravity::Center;
+        self.put_pixels_in(&data, dim, area, mode, gravity, filter)
+    }
+
+    /// Works like [`Self::put_image_scaled`] but converts the image into
+    /// given scratch buffer which can be reused between calls.
+    fn put_image_scaled_reusing<'b>(
+        &self,
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+        scratch: &mut Vec<u32>,
+    ) -> Result<Rect> {
+        let dim = self.convert_into(img, scratch)?;
+        let (width, height) = self.dimensions();
+        let area = (0, 0, width, height);
+        let gravity = img::Gravity::Center;
+        self.put_pixels_in(scratch, dim, area, mode, gravity, filter)
+    }
+
+    /// Scales an image according to given mode and puts it in given area of
+    /// the surface.
+    ///
+    /// Unlike [`Self::put_placed`], supports [`img::ScaleMode::Tile`] mode.
+    fn put_pixels_in(
+        &self,
+        data: &[u32],
+        dim: (u16, u16),
+        area: (i16, i16, u16, u16),
+        mode: img::ScaleMode,
+        gravity: img::Gravity,
+        filter: img::Filter,
+    ) -> Result<Rect> {
+        if mode == img::ScaleMode::Tile {
+            return self.put_tiled_impl(area, (area.0, area.1), dim, data);
+        }
+        self.put_placed(data, dim, area, mode, gravity, filter)
+    }
+
+    /// See [`crate::RootPixmap::put_image_spanned`].
+    fn put_image_spanned<'b>(
+        &self,
+        areas: &[(i16, i16, u16, u16)],
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let Some(bbox) = crate::bounding_box(areas.iter().copied()) else {
+            return Ok(());
+        };
+        let (dim, buffer) = self.convert(img)?;
+        let pixels = to_pixels(buffer.as_ref());
+        if mode == img::ScaleMode::Tile {
+            let (width, height) = self.dimensions();
+            let area = (0, 0, width, height);
+            self.put_tiled_impl(area, (bbox.0, bbox.1), dim, &pixels)?;
+            return Ok(());
+        }
+        let placement = mode.place(dim, (bbox.2, bbox.3), img::Gravity::Center);
+        for &area in areas {
+            // Placement relative to the area rather than the bounding box.
+            let placement = img::Placement {
+                x: placement.x - i64::from(area.0) + i64::from(bbox.0),
+                y: placement.y - i64::from(area.1) + i64::from(bbox.1),
+                ..placement
+            };
+            self.put_resampled(&pixels, dim, placement, area, filter, 0)?;
+        }
+        Ok(())
+    }
+
+    /// See [`crate::RootPixmap::put_image_blurred_fit`].
+    fn put_image_blurred_fit<'b>(
+        &self,
+        radius: u16,
+        filter: img::Filter,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let (width, height) = self.dimensions();
+        let area = (0, 0, width, height);
+        let mode = img::ScaleMode::Fit;
+        self.put_wallpaper(area, mode, filter, 0, radius, img)
+    }
+
+    /// Scales an image according to given mode and puts it in given area of
+    /// the surface painting parts of the area not covered by it.
+    ///
+    /// If `blur_radius` is non-zero, the area is first covered by a copy of
+    /// the image scaled as in [`img::ScaleMode::Fill`] mode and blurred with
+    /// a box blur of given radius.  Otherwise, with [`img::ScaleMode::Fit`]
+    /// and [`img::ScaleMode::Center`] modes, the area is filled with
+    /// `background` colour.  See [`crate::WallpaperBuilder`].
+    fn put_wallpaper<'b>(
+        &self,
+        area: (i16, i16, u16, u16),
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        background: u32,
+        blur_radius: u16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let (dim, buffer) = self.convert(img)?;
+        let pixels = to_pixels(buffer.as_ref());
+        self.put_wallpaper_pixels(
+            &pixels,
+            dim,
+            area,
+            mode,
+            filter,
+            background,
+            blur_radius,
+        )
+    }
+
+    /// Works like [`Self::put_wallpaper`] but takes a `dim.0`×`dim.1` image
+    /// already in the surface’s pixel format.
+    #[allow(clippy::too_many_arguments)]
+    fn put_wallpaper_pixels(
+        &self,
+        pixels: &[u32],
+        dim: (u16, u16),
+        area: (i16, i16, u16, u16),
+        mode: img::ScaleMode,
+        filter: img::Filter,
+        background: u32,
+        blur_radius: u16,
+    ) -> Result {
+        if blur_radius != 0 {
+            let (fill, center) = (img::ScaleMode::Fill, img::Gravity::Center);
+            let place = fill.place(dim, (area.2, area.3), center);
+            let radius = blur_radius;
+            self.put_resampled(pixels, dim, place, area, filter, radius)?;
+        } else if matches!(mode, img::ScaleMode::Fit | img::ScaleMode::Center) {
+            let (x, y, width, height) = area;
+            self.fill_rect(x, y, width, height, background)?;
+        }
+        let gravity = img::Gravity::Center;
+        self.put_pixels_in(pixels, dim, area, mode, gravity, filter)?;
+        Ok(())
+    }
+
+    /// Scales an image according to given mode and puts it in given area of
+    /// the surface.
+    ///
+    /// `data` is a `dim.0`×`dim.1` image in the surface’s pixel format and
+    /// `area` is `(x, y, width, height)` rectangle the image is fitted into.
+    /// `gravity` anchors the scaled image within the area.  `mode` must not be
+    /// [`img::ScaleMode::Tile`].
+    ///
+    /// Returns the rectangle the image was drawn onto which is empty if the
+    /// scaled image doesn’t overlap the area.
+    fn put_placed(
+        &self,
+        data: &[u32],
+        dim: (u16, u16),
+        area: (i16, i16, u16, u16),
+        mode: img::ScaleMode,
+        gravity: img::Gravity,
+        filter: img::Filter,
+    ) -> Result<Rect> {
+        let placement = mode.place(dim, (area.2, area.3), gravity);
+        self.put_resampled(data, dim, placement, area, filter, 0)
+    }
+
+    /// Resamples an image according to given placement and puts the part of
+    /// it which lies in given area on the surface.
+    ///
+    /// `placement` is relative to the area’s top-left corner.  If
+    /// `blur_radius` is non-zero, the scaled image is blurred with a box blur
+    /// of given radius.  Otherwise works like [`Self::put_placed`].
+    fn put_resampled(
+        &self,
+        data: &[u32],
+        dim: (u16, u16),
+        placement: img::Placement,
+        area: (i16, i16, u16, u16),
+        filter: img::Filter,
+        blur_radius: u16,
+    ) -> Result<Rect> {
+        if dim.0 == 0 || dim.1 == 0 {
+            return Ok(Rect::default());
+        }
+        let (area_x, area_y, width, height) = area;
+        let shifts = self.rgb_shifts();
+        let size = (width, height);
+        match img::resample(data, dim, placement, size, shifts, filter) {
+            Some((x, y, width, height, mut data)) => {
+                img::box_blur(&mut data, (width, height), shifts, blur_radius);
+                let rect = Rect {
+                    x: area_x.wrapping_add_unsigned(x),
+                    y: area_y.wrapping_add_unsigned(y),
+                    width,
+                    height,
+                };
+                self.put_raw_impl(
+                    rect.x,
+                    rect.y,
+                    width,
+                    height,
+                    bytemuck::must_cast_slice(data.as_slice()),
+                )?;
+                Ok(rect)
+            }
+            None => Ok(Rect::default()),
+        }
+    }
+
+    /// See [`crate::RootPixmap::put_raw`].
+    fn put_raw(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u32],
+    ) -> Result {
+        if usize::from(width) * usize::from(height) == data.len() {
+            let data = bytemuck::must_cast_slice(data);
+            self.put_raw_impl(dst_x, dst_y, width, height, data)
+        } else {
+            Err(Error::BadBufferSize(data.len() * 4, width, height))
+        }
+    }
+
+    /// See [`crate::RootPixmap::put_raw_bytes`].
+    fn put_raw_bytes(
+        &self,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> Result {
+        if usize::from(width) * usize::from(height) * 4 == data.len() {
+            self.put_raw_impl(dst_x, dst_y, width, height, data)
+        } else {
+            Err(Error::BadBufferSize(data.len(), width, height))
+        }
+    }
+
+    /// See [`crate::RootPixmap::put_image_tiled`].
+    fn put_image_tiled<'b>(
+        &self,
+        offset_x: i16,
+        offset_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let (dim, buffer) = self.convert(img)?;
+        let pixels = to_pixels(buffer.as_ref());
+        let (width, height) = self.dimensions();
+        let area = (0, 0, width, height);
+        self.put_tiled_impl(area, (offset_x, offset_y), dim, &pixels)?;
+        Ok(())
+    }
+
+    /// See [`crate::RootPixmap::put_image_tiled_mirrored`].
+    fn put_image_tiled_mirrored<'b>(
+        &self,
+        offset_x: i16,
+        offset_y: i16,
+        img: impl img::IntoXBuffer<'b>,
+    ) -> Result {
+        let (dim, buffer) = self.convert(img)?;
+        let pixels = to_pixels(buffer.as_ref());
+        let (dim, tile) = img::mirror_tile(&pixels, dim)?;
+        let (width, height) = self.dimensions();
+        let area = (0, 0, width, height);
+        self.put_tiled_impl(area, (offset_x, offset_y), dim, &tile)?;
+        Ok(())
+    }
+
+    /// Repeats an image across given area of the surface.
+    ///
+    /// `data` is a `dim.0`×`dim.1` image in the surface’s pixel format and
+    /// `area` is `(x, y, width, height)` rectangle to fill.  One of the tiles
+    /// is placed with its top-left corner at `offset` (which may lie outside
+    /// of the area).  Tiles which fall partially outside of the area or the
+    /// surface are clipped.
+    ///
+    /// Returns the rectangle the tiles were drawn onto, i.e. the area clipped
+    /// to the surface, or an empty rectangle if the image is empty.
+    fn put_tiled_impl(
+        &self,
+        area: (i16, i16, u16, u16),
+        offset: (i16, i16),
+        dim: (u16, u16),
+        data: &[u32],
+    ) -> Result<Rect> {
+        let (tile_w, tile_h) = (i32::from(dim.0), i32::from(dim.1));
+        let (width, height) = self.dimensions();
+        let (x, y) = (i32::from(area.0), i32::from(area.1));
+        let (left, top) = (x.max(0), y.max(0));
+        let right = (x + i32::from(area.2)).min(i32::from(width));
+        let bottom = (y + i32::from(area.3)).min(i32::from(height));
+        if tile_w == 0 || tile_h == 0 || left >= right || top >= bottom {
+            return Ok(Rect::default());
+        }
+
+        // Construct a horizontal band as wide as the clipped area and as tall
+        // as the tile.  The band is then drawn at each vertical tile position.
+        let width = (right - left) as usize;
+        let start = (left - i32::from(offset.0)).rem_euclid(tile_w) as usize;
+        let mut band = Vec::<u32>::with_capacity(width * tile_h as usize);
+        for row in data.chunks_exact(tile_w as usize) {
+            band.extend(row.iter().cycle().skip(start).take(width));
+        }
+
+        // The first band may start in the middle of the tile.
+        let mut row = (top - i32::from(offset.1)).rem_euclid(tile_h);
+        let mut y = top;
+        while y < bottom {
+            let rows = (tile_h - row).min(bottom - y);
+            let lines = &band[row as usize * width..][..rows as usize * width];
+            self.put_raw_impl(
+                left as i16,
+                y as i16,
+                width as u16,
+                rows as u16,
+                bytemuck::must_cast_slice(lines),
+            )?;
+            y += rows;
+            row = 0;
+        }
+        Ok(Rect {
+            x: left as i16,
+            y: top as i16,
+            width: width as u16,
+            height: (bottom - top) as u16,
+        })
+    }
+
+    /// See [`crate::RootPixmap::fill_solid`].
+    fn fill_solid(&self, colour: u32) -> Result {
+        let (width, height) = self.dimensions();
+        let rect = x::Rectangle { x: 0, y: 0, width, height };
+        self.fill_rects_impl(&[rect], colour)
+    }
+
+    /// See [`crate::RootPixmap::fill_rect`].
+    fn fill_rect(
+        &self,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        colour: u32,
+    ) -> Result {
+        let rect = check_rect(x, y, width, height, self.dimensions())?;
+        self.fill_rects_impl(&[rect], colour)
+    }
+
+    /// See [`crate::RootPixmap::fill_checkerboard`].
+    fn fill_checkerboard(&self, a: u32, b: u32, cell: u16) -> Result {
+        if cell == 0 {
+            return Err(Error::ZeroCellSize);
+        }
+        self.fill_solid(a)?;
+        let (width, height) = self.dimensions();
+        // Coordinates past i16::MAX can’t be addressed by X requests.
+        let width = u32::from(width.min(i16::MAX as u16));
+        let height = u32::from(height.min(i16::MAX as u16));
+        let cell = u32::from(cell);
+        let mut rects = Vec::new();
+        for (row, y) in (0..height).step_by(cell as usize).enumerate() {
+            let start = if row % 2 == 0 { cell } else { 0 };
+            for x in (start..width).step_by(2 * cell as usize) {
+                rects.push(x::Rectangle {
+                    x: x as i16,
+                    y: y as i16,
+                    width: cell.min(width - x) as u16,
+                    height: cell.min(height - y) as u16,
+                });
+            }
+        }
+        self.fill_rects_impl(&rects, b)
+    }
+
+    /// See [`crate::RootPixmap::fill_linear_gradient`].
+    fn fill_linear_gradient(
+        &self,
+        start: (u8, u8, u8),
+        end: (u8, u8, u8),
+        direction: img::GradientDirection,
+        dither: img::Dither,
+    ) -> Result {
+        let (width, height) = self.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let horizontal = direction == img::GradientDirection::Horizontal;
+        let colour = |x, y| {
+            let (pos, len) = if horizontal { (x, width) } else { (y, height) };
+            let (r, g, b) = match dither {
+                img::Dither::None => img::interpolate(start, end, pos, len),
+                img::Dither::Ordered => {
+                    img::interpolate_dithered(start, end, pos, len, (x, y))
+                }
+            };
+            self.rgb_shifts().from_rgb(r, g, b)
+        };
+
+        // Send the gradient in bands of multiple scanlines to reduce number
+        // of requests.  The same buffer is reused for all bands.
+        const BAND_PIXELS: usize = 1 << 16;
+        let stride = usize::from(width);
+        let rows = (BAND_PIXELS / stride.max(1)).clamp(1, usize::from(height));
+        let mut band = vec![0; stride * rows];
+        if horizontal && dither == img::Dither::None {
+            let line = (0..width).map(|x| colour(x, 0));
+            for row in band.chunks_exact_mut(stride) {
+                row.iter_mut().zip(line.clone()).for_each(|(px, c)| *px = c);
+            }
+        }
+
+        for y in (0..height).step_by(rows) {
+            let rows = (height - y).min(rows as u16);
+            let band_rows = band.chunks_exact_mut(stride).zip(y..y + rows);
+            if dither == img::Dither::Ordered {
+                // Dithered pixels differ even along the gradient’s solid
+                // axis so everything needs to be recomputed.
+                for (row, y) in band_rows {
+                    for (px, x) in row.iter_mut().zip(0..) {
+                        *px = colour(x, y);
+                    }
+                }
+            } else if !horizontal {
+                for (row, y) in band_rows {
+                    // Each row is filled with a single colour so looking at
+                    // the first pixel is enough to tell if it needs updating.
+                    let c = colour(0, y);
+                    if row[0] != c {
+                        row.fill(c);
+                    }
+                }
+            }
+            let data = &band[..stride * usize::from(rows)];
+            self.put_raw_impl(
+                0,
+                y as i16,
+                width,
+                rows,
+                bytemuck::must_cast_slice(data),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Converts image into pixel format described by given shifts.
+///
+/// Verifies that the returned buffer matches the image dimensions.
+fn convert_to<'b, I: img::IntoXBuffer<'b>>(
+    img: I,
+    rgb_shifts: img::RgbShifts,
+) -> Result<((u16, u16), I::Buffer)> {
+    let (width, height) = img.dimensions()?;
+    let buffer = img.into_x_buffer(rgb_shifts)?;
+    let len = buffer.as_ref().len();
+    if usize::from(width) * usize::from(height) * 4 == len {
+        Ok(((width, height), buffer))
+    } else {
+        Err(Error::BadBufferSize(len, width, height))
+    }
+}
+
+
+#[test]
+fn test_put_image_scaled() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2];
+    let img = || img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(4, 4, img::RgbShifts::XRGB8888);
+    let filter = img::Filter::Nearest;
+
+    let rect =
+        surface.put_image_scaled(img::ScaleMode::Fit, filter, img()).unwrap();
+    assert_eq!(Rect { x: 0, y: 1, width: 4, height: 2 }, rect);
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0, 0, 0,
+        0x010101, 0x010101, 0x020202, 0x020202,
+        0x010101, 0x010101, 0x020202, 0x020202,
+        0, 0, 0, 0,
+    ], &*surface.pixels());
+
+    surface.fill_solid(0xFF).unwrap();
+    let rect = surface
+        .put_image_scaled(img::ScaleMode::Center, filter, img())
+        .unwrap();
+    assert_eq!(Rect::from((1, 1, 2, 1)), rect);
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0xFF, 0xFF, 0xFF, 0xFF,
+        0xFF, 0x010101, 0x020202, 0xFF,
+        0xFF, 0xFF, 0xFF, 0xFF,
+        0xFF, 0xFF, 0xFF, 0xFF,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_blurred_fit() {
+    let data: &[u8] = &[0, 0, 0, 90, 90, 90];
+    let img = img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(4, 4, img::RgbShifts::XRGB8888);
+    let filter = img::Filter::Nearest;
+
+    Surface::put_image_blurred_fit(&surface, 1, filter, img).unwrap();
+    // The image scaled to cover the surface is 8×4 with its middle 4×4 part
+    // visible, i.e. two columns of each colour.  Blurring it only affects
+    // columns at the border of the two colours.  In the middle two rows,
+    // the image is drawn scaled to fit.
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0x1E1E1E, 0x3C3C3C, 0x5A5A5A,
+        0, 0, 0x5A5A5A, 0x5A5A5A,
+        0, 0, 0x5A5A5A, 0x5A5A5A,
+        0, 0x1E1E1E, 0x3C3C3C, 0x5A5A5A,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_wallpaper() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2];
+    let img = || img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(4, 4, img::RgbShifts::XRGB8888);
+    let filter = img::Filter::Nearest;
+    surface.fill_solid(0xFF).unwrap();
+
+    let mode = img::ScaleMode::Center;
+    let area = (0, 1, 4, 3);
+    surface.put_wallpaper(area, mode, filter, 0x42, 0, img()).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0xFF, 0xFF, 0xFF, 0xFF,
+        0x42, 0x42, 0x42, 0x42,
+        0x42, 0x010101, 0x020202, 0x42,
+        0x42, 0x42, 0x42, 0x42,
+    ], &*surface.pixels());
+
+    // With blur, background colour is ignored and the image scaled to fill
+    // the area shows around it.
+    let mode = img::ScaleMode::Fit;
+    let area = (0, 0, 2, 4);
+    surface.put_wallpaper(area, mode, filter, 0x42, 1, img()).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0x010101, 0x020202, 0xFF, 0xFF,
+        0x010101, 0x020202, 0x42, 0x42,
+        0x010101, 0x020202, 0x020202, 0x42,
+        0x010101, 0x020202, 0x42, 0x42,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_scaled_with_gravity() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2];
+    let img = || img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(5, 2, img::RgbShifts::XRGB8888);
+    let (center, filter) = (img::ScaleMode::Center, img::Filter::Nearest);
+
+    let put = |gravity| {
+        surface.fill_solid(0).unwrap();
+        surface
+            .put_image_scaled_with_gravity(center, gravity, filter, img())
+            .unwrap()
+    };
+    let rect = put(img::Gravity::SouthEast);
+    assert_eq!(Rect::from((3, 1, 2, 1)), rect);
+    assert_eq!(
+        vec![0, 0, 0, 0, 0, 0, 0, 0, 0x010101, 0x020202],
+        *surface.pixels()
+    );
+    let rect = put(img::Gravity::NorthWest);
+    assert_eq!(Rect::from((0, 0, 2, 1)), rect);
+    // Odd leftover column ends up on the right.
+    let rect = put(img::Gravity::North);
+    assert_eq!(Rect::from((1, 0, 2, 1)), rect);
+    assert_eq!(rect, put(img::Gravity::Center));
+}
+
+#[test]
+fn test_put_image_in() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2];
+    let img = || img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(4, 3, img::RgbShifts::XRGB8888);
+    let filter = img::Filter::Nearest;
+
+    let fit = img::ScaleMode::Fit;
+    Surface::put_image_in(&surface, (2, 0, 2, 3), fit, filter, img()).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0, 0, 0,
+        0, 0, 0x010101, 0x020202,
+        0, 0, 0, 0,
+    ], &*surface.pixels());
+
+    let fill = img::ScaleMode::Fill;
+    let rect =
+        Surface::put_image_in(&surface, (-1, 2, 3, 1), fill, filter, img())
+            .unwrap();
+    assert_eq!(Rect::from((-1, 2, 3, 1)), rect);
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0, 0, 0,
+        0, 0, 0x010101, 0x020202,
+        0x010101, 0x020202, 0, 0,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_rotated_in() {
+    // 3×2 image:
+    //   1 2 3
+    //   4 5 6
+    let data = (1..=6).flat_map(|v| [v, v, v]).collect::<Vec<u8>>();
+    let img = img::RgbImage::new(3, 2, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(3, 3, img::RgbShifts::XRGB8888);
+    let (stretch, filter) = (img::ScaleMode::Stretch, img::Filter::Nearest);
+    let rotation = img::Rotation::Cw90;
+    let rect = Surface::put_image_rotated_in(
+        &surface,
+        (1, 0, 2, 3),
+        rotation,
+        stretch,
+        filter,
+        img,
+    )
+    .unwrap();
+    assert_eq!(Rect::from((1, 0, 2, 3)), rect);
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0x040404, 0x010101,
+        0, 0x050505, 0x020202,
+        0, 0x060606, 0x030303,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_tiled() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2];
+    let img = img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    surface.put_image_tiled(1, 1, img).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0x020202, 0x010101, 0x020202,
+        0x020202, 0x010101, 0x020202,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_tiled_in_area() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4];
+    let img = || img::RgbImage::new(2, 2, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(5, 4, img::RgbShifts::XRGB8888);
+    let tile = img::ScaleMode::Tile;
+    let filter = img::Filter::Nearest;
+
+    // Tiles are aligned with the area’s corner and clipped to the area.
+    let rect =
+        Surface::put_image_in(&surface, (1, 1, 3, 3), tile, filter, img())
+            .unwrap();
+    assert_eq!(crate::Rect { x: 1, y: 1, width: 3, height: 3 }, rect);
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0, 0,        0,        0,        0,
+        0, 0x010101, 0x020202, 0x010101, 0,
+        0, 0x030303, 0x040404, 0x030303, 0,
+        0, 0x010101, 0x020202, 0x010101, 0,
+    ], &*surface.pixels());
+
+    // Area partially outside of the surface is clipped to the surface.
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    let rect =
+        Surface::put_image_in(&surface, (-1, 1, 3, 3), tile, filter, img())
+            .unwrap();
+    assert_eq!(crate::Rect { x: 0, y: 1, width: 2, height: 1 }, rect);
+    assert_eq!(vec![0, 0, 0, 0x020202, 0x010101, 0], &*surface.pixels());
+}
+
+#[test]
+fn test_degenerate_images() {
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    let filter = img::Filter::Nearest;
+    surface.fill_solid(0x42).unwrap();
+
+    // Images with zero width or height draw nothing.
+    for (width, height) in [(0, 0), (0, 2), (2, 0)] {
+        let data: &[u8] = &[];
+        let img = || img::RgbImage::new(width, height, data.into()).unwrap();
+        surface.put_image(1, 1, img()).unwrap();
+        surface.put_image_tiled(0, 0, img()).unwrap();
+        for mode in [
+            img::ScaleMode::Fill,
+            img::ScaleMode::Fit,
+            img::ScaleMode::Stretch,
+            img::ScaleMode::Center,
+            img::ScaleMode::Tile,
+        ] {
+            let rect = surface.put_image_scaled(mode, filter, img()).unwrap();
+            assert!(mode == img::ScaleMode::Tile || rect.is_empty());
+        }
+        surface.put_image_blurred_fit(1, filter, img()).unwrap();
+        surface.put_blended(0, 0, img(), 0.5).unwrap();
+        surface.put_raw(0, 0, width as u16, height as u16, &[]).unwrap();
+        assert_eq!(vec![0x42; 6], &*surface.pixels());
+    }
+
+    // 1×1 image can be used as a solid fill.
+    let data: &[u8] = &[1, 2, 3];
+    let img = img::RgbImage::new(1, 1, data.into()).unwrap();
+    surface.put_image_tiled(0, 0, img).unwrap();
+    assert_eq!(vec![0x010203; 6], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_tiled_mirrored() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2];
+    let img = img::RgbImage::new(2, 1, data.into()).unwrap();
+    let surface = img::MemoryCanvas::new(5, 2, img::RgbShifts::XRGB8888);
+    surface.put_image_tiled_mirrored(1, 0, img).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0x010101, 0x010101, 0x020202, 0x020202, 0x010101,
+        0x010101, 0x010101, 0x020202, 0x020202, 0x010101,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_fill_linear_gradient() {
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    let (start, end) = ((0, 0, 0), (0, 0, 200));
+    let fill = |direction, dither| {
+        surface.fill_linear_gradient(start, end, direction, dither).unwrap();
+        surface.pixels().to_vec()
+    };
+    let (horizontal, vertical) =
+        (img::GradientDirection::Horizontal, img::GradientDirection::Vertical);
+
+    let got = fill(horizontal, img::Dither::None);
+    assert_eq!(vec![0, 100, 200, 0, 100, 200], got);
+    let got = fill(vertical, img::Dither::None);
+    assert_eq!(vec![0, 0, 0, 200, 200, 200], got);
+
+    // Endpoints are exact so dithering only affects the middle column.
+    let (start, end) = ((0, 0, 0), (0, 0, 3));
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    surface
+        .fill_linear_gradient(start, end, horizontal, img::Dither::Ordered)
+        .unwrap();
+    // Bayer thresholds for (1, 0) and (1, 1) are 32 and 16 while the middle
+    // value is 1.5, i.e. 96/64.
+    assert_eq!(vec![0, 2, 3, 0, 1, 3], &*surface.pixels());
+}
+
+#[test]
+fn test_fill_rect() {
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    surface.fill_rect(1, 0, 2, 1, 7).unwrap();
+    assert_eq!(vec![0, 7, 7, 0, 0, 0], &*surface.pixels());
+    assert!(matches!(
+        surface.fill_rect(2, 0, 2, 1, 7),
+        Err(Error::RectOutOfBounds(2, 0, 2, 1))
+    ));
+}
+
+#[test]
+fn test_fill_checkerboard() {
+    let surface = img::MemoryCanvas::new(5, 3, img::RgbShifts::XRGB8888);
+    surface.fill_checkerboard(1, 2, 2).unwrap();
+    #[rustfmt::skip]
+    let want = [
+        1, 1, 2, 2, 1,
+        1, 1, 2, 2, 1,
+        2, 2, 1, 1, 2,
+    ];
+    assert_eq!(want, &*surface.pixels());
+    surface.fill_checkerboard(3, 4, 1).unwrap();
+    assert_eq!([3, 4, 3, 4, 3, 4, 3, 4, 3, 4], surface.pixels()[..10]);
+    assert!(matches!(
+        surface.fill_checkerboard(1, 2, 0),
+        Err(Error::ZeroCellSize)
+    ));
+}
+
+#[test]
+fn test_composite() {
+    let surface = img::MemoryCanvas::new(3, 2, img::RgbShifts::XRGB8888);
+    surface.fill_solid(0x646464).unwrap();
+
+    let data: &[u8] = &[200, 0, 0, 255, 0, 200, 0, 0];
+    let photo = img::RgbaImage::new(2, 1, data.into()).unwrap();
+    let data: &[u8] = &[0, 0, 200, 255, 0, 0, 200, 255];
+    let logo = img::RgbaImage::new(1, 2, data.into()).unwrap();
+    let layer = |x, y, opacity, img| img::Layer { x, y, opacity, img };
+    surface
+        .composite(&[
+            layer(0, 0, 1.0, photo.clone()),
+            layer(2, -1, 0.5, logo.clone()),
+            layer(5, 0, 1.0, logo.clone()),
+            layer(0, 1, 0.0, photo),
+            layer(0, 1, f32::NAN, logo),
+        ])
+        .unwrap();
+    #[rustfmt::skip]
+    assert_eq!(vec![
+        0xC80000, 0x646464, 0x323296,
+        0x646464, 0x646464, 0x646464,
+    ], &*surface.pixels());
+}
+
+#[test]
+fn test_put_image_spanned() {
+    let data: &[u8] = &[1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4];
+    let img = || img::RgbImage::new(4, 1, data.into()).unwrap();
+    let filter = img::Filter::Nearest;
+    let stretch = img::ScaleMode::Stretch;
+
+    // Two 2×2 monitors with a 2-pixel gap between them.  The image is
+    // stretched to 6×2 bounding box and the middle part is skipped.
+    let surface = img::MemoryCanvas::new(6, 2, img::RgbShifts::XRGB8888);
+    let areas = [(0, 0, 2, 2), (4, 0, 2, 2)];
+    Surface::put_image_spanned(&surface, &areas, stretch, filter, img())
+        .unwrap();
+
+    let want = img::MemoryCanvas::new(6, 2, img::RgbShifts::XRGB8888);
+    want.put_image_scaled(stretch, filter, img()).unwrap();
+    want.fill_rect(2, 0, 2, 2, 0).unwrap();
+    assert_eq!(*want.pixels(), *surface.pixels());
+    assert_eq!(0x010101, surface.pixels()[0]);
+    assert_eq!(0x040404, surface.pixels()[5]);
+}
+
+#[test]
+fn test_put_raw_bytes() {
+    let surface = img::MemoryCanvas::new(2, 2, img::RgbShifts::XRGB8888);
+    let data = [0x010203u32, 0x040506];
+    let bytes: &[u8] = bytemuck::must_cast_slice(&data[..]);
+    surface.put_raw_bytes(0, 1, 2, 1, bytes).unwrap();
+    assert_eq!(vec![0, 0, 0x010203, 0x040506], &*surface.pixels());
+    assert!(matches!(
+        surface.put_raw_bytes(0, 0, 2, 1, &bytes[1..]),
+        Err(Error::BadBufferSize(7, 2, 1))
+    ));
+}
+
+#[test]
+fn test_put_blended() {
+    let surface = img::MemoryCanvas::new(2, 2, img::RgbShifts::XRGB8888);
+    surface.fill_solid(0x646464).unwrap();
+    let data: &[u8] = &[200, 0, 100, 0, 0, 0];
+    let img = || img::RgbImage::new(2, 1, data.into()).unwrap();
+    Surface::put_blended(&surface, 0, 1, img(), 0.5).unwrap();
+    assert_eq!(vec![0x646464, 0x646464, 0x963264, 0x323232], *surface.pixels());
+    assert!(Surface::put_blended(&surface, 1, 1, img(), 0.5).is_err());
+}
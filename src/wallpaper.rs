@@ -0,0 +1,191 @@
+//! High-level interface for setting desktop background.
+
+use crate::surface::Surface;
+use crate::{Display, Monitor, Result, RootPixmap, img};
+
+/// Configures how an image is drawn when setting it as the desktop
+/// background.
+///
+/// Obtained with [`Display::wallpaper`].  Options are set with chainable
+/// methods and the image is drawn and set as the background of the default
+/// screen with [`Self::set`].  This is a convenience layer on top of
+/// [`crate::RootPixmap`] which remains available for finer control.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn load() -> setroot::img::RgbImage<'static, u8> { todo!() }
+/// let display = setroot::Display::open().unwrap();
+/// let monitor = display.primary_monitor().unwrap();
+/// let mut wallpaper = display
+///     .wallpaper()
+///     .mode(setroot::img::ScaleMode::Fit)
+///     .background((32, 32, 32));
+/// if let Some(monitor) = monitor {
+///     wallpaper = wallpaper.monitor(monitor);
+/// }
+/// wallpaper.set(load()).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct WallpaperBuilder<'a> {
+    display: &'a Display,
+    mode: img::ScaleMode,
+    filter: img::Filter,
+    background: (u8, u8, u8),
+    monitor: Option<Monitor>,
+    screen: Option<i32>,
+    blur: u16,
+}
+
+impl<'a> WallpaperBuilder<'a> {
+    pub(crate) fn new(display: &'a Display) -> Self {
+        Self {
+            display,
+            mode: img::ScaleMode::Fill,
+            filter: img::Filter::Lanczos3,
+            background: (0, 0, 0),
+            monitor: None,
+            screen: None,
+            blur: 0,
+        }
+    }
+
+    /// Sets how the image is scaled to cover the screen or monitor.
+    ///
+    /// See [`img::ScaleMode`].  Defaults to [`img::ScaleMode::Fill`].
+    pub fn mode(mut self, mode: img::ScaleMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets filter used when resizing the image.
+    ///
+    /// Defaults to [`img::Filter::Lanczos3`] which falls back to
+    /// nearest-neighbour filter if `resize` feature is disabled.
+    pub fn filter(mut self, filter: img::Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets colour of the parts of the screen or monitor not covered by the
+    /// image.
+    ///
+    /// Only relevant with [`img::ScaleMode::Fit`] and
+    /// [`img::ScaleMode::Center`] modes and ignored if blur is enabled.
+    /// Defaults to black.
+    pub fn background(mut self, colour: (u8, u8, u8)) -> Self {
+        self.background = colour;
+        self
+    }
+
+    /// Restricts the image to given monitor.
+    ///
+    /// The image is scaled to cover the monitor rather than the entire screen
+    /// (see [`crate::RootPixmap::put_image_on_monitor`]).  The rest of the
+    /// screen keeps showing the current background (as published in
+    /// `_XROOTPMAP_ID` atom) or is black if there’s none.  If the monitor is
+    /// rotated, the image is rotated by the inverse rotation first.  By
+    /// default, the image covers the entire screen.
+    pub fn monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// Sets the background of screen with given number rather than of the
+    /// default one.
+    ///
+    /// See [`Display::root_pixmap_for_screen`].
+    pub(crate) fn screen(mut self, screen_num: i32) -> Self {
+        self.screen = Some(screen_num);
+        self
+    }
+
+    /// Fills parts not covered by the image with its blurred copy.
+    ///
+    /// With non-zero `radius`, a copy of the image scaled as in
+    /// [`img::ScaleMode::Fill`] mode and blurred with a box blur of given
+    /// radius is drawn underneath the image (see
+    /// [`crate::RootPixmap::put_image_blurred_fit`]).  Zero, which is the
+    /// default, disables the blur.
+    pub fn blur(mut self, radius: u16) -> Self {
+        self.blur = radius;
+        self
+    }
+
+    /// Draws the image according to the configuration and sets it as the
+    /// desktop background with [`crate::RootPixmap::set_background`].
+    ///
+    /// Returns an error if the dimensions of the image are too large (or on
+    /// protocol error).
+    pub fn set<'b>(&self, img: impl img::IntoXBuffer<'b>) -> Result {
+        let (mode, filter, radius) = (self.mode, self.filter, self.blur);
+        let rotation = self.rotation();
+        self.draw(|pixmap, area, background| {
+            if rotation == img::Rotation::None {
+                return pixmap.put_wallpaper(
+                    area, mode, filter, background, radius, img,
+                );
+            }
+            let (dim, data) = pixmap.convert_rotated(rotation, img)?;
+            pixmap.put_wallpaper_pixels(
+                &data, dim, area, mode, filter, background, radius,
+            )
+        })
+    }
+
+    /// Works like [`Self::set`] but converts the image into given scratch
+    /// buffer which can be reused between calls.
+    pub(crate) fn set_reusing<'b>(
+        &self,
+        img: impl img::IntoXBuffer<'b>,
+        scratch: &mut Vec<u32>,
+    ) -> Result {
+        let (mode, filter, radius) = (self.mode, self.filter, self.blur);
+        let rotation = self.rotation();
+        self.draw(|pixmap, area, background| {
+            let dim = pixmap.convert_into(img, scratch)?;
+            img::rotate_in_place(scratch, dim, rotation);
+            let dim = rotation.rotate_dimensions(dim);
+            pixmap.put_wallpaper_pixels(
+                scratch, dim, area, mode, filter, background, radius,
+            )
+        })
+    }
+
+    /// Returns rotation the image needs to be drawn with to appear upright on
+    /// the configured monitor.
+    fn rotation(&self) -> img::Rotation {
+        self.monitor
+            .as_ref()
+            .map_or(img::Rotation::None, |monitor| monitor.rotation.inverse())
+    }
+
+    /// Prepares the root pixmap, calls `put` to draw the image on it and sets
+    /// it as the desktop background.
+    ///
+    /// `put` is passed the pixmap, the area the image should cover and the
+    /// background colour in the pixmap’s pixel format.
+    fn draw(
+        &self,
+        put: impl FnOnce(&RootPixmap<'_>, (i16, i16, u16, u16), u32) -> Result,
+    ) -> Result {
+        let pixmap = match self.screen {
+            Some(num) => self.display.root_pixmap_for_screen(num)?,
+            None => self.display.root_pixmap()?,
+        };
+        let area = match &self.monitor {
+            Some(monitor) => {
+                pixmap.copy_current_background()?;
+                monitor.rect()
+            }
+            None => {
+                let (width, height) = pixmap.dimensions();
+                (0, 0, width, height)
+            }
+        };
+        let (red, green, blue) = self.background;
+        let background = pixmap.rgb_shifts().from_rgb(red, green, blue);
+        put(&pixmap, area, background)?;
+        pixmap.set_background()
+    }
+}